@@ -4,7 +4,13 @@ use std::fs::create_dir_all;
 use cosmwasm_schema::{export_schema, remove_schemas, schema_for};
 
 use primitive_contract::msg::{
-    ConfigResponse, ExecuteMsg, GetValueResponse, InstantiateMsg, QueryMsg,
+    AggregateResponse, ArithmeticResponse, BoundsResponse, ChangesSinceResponse, ConfigResponse,
+    ConstraintsResponse, DiffSnapshotResponse, ExecuteMsg, ExportMetadataResponse,
+    ExportStateResponse, FindKeysByValueResponse, GetValueResponse, GetValueWithProofResponse,
+    InstantiateMsg, KeyInfoResponse, ListByTagResponse, ListChildrenResponse, ListSchemasResponse,
+    ListSnapshotsResponse, ListValuesResponse, MigrateMsg, QueryMsg, RankOfResponse,
+    RestoreSnapshotResponse, SchemaResponse, StateRootResponse, TopNResponse, TypeLockResponse,
+    VecContainsResponse, VecLengthResponse, VecSliceResponse,
 };
 use primitive_contract::state::Config;
 
@@ -17,7 +23,33 @@ fn main() {
     export_schema(&schema_for!(InstantiateMsg), &out_dir);
     export_schema(&schema_for!(ExecuteMsg), &out_dir);
     export_schema(&schema_for!(QueryMsg), &out_dir);
+    export_schema(&schema_for!(MigrateMsg), &out_dir);
     export_schema(&schema_for!(Config), &out_dir);
     export_schema(&schema_for!(GetValueResponse), &out_dir);
     export_schema(&schema_for!(ConfigResponse), &out_dir);
+    export_schema(&schema_for!(KeyInfoResponse), &out_dir);
+    export_schema(&schema_for!(ExportStateResponse), &out_dir);
+    export_schema(&schema_for!(ExportMetadataResponse), &out_dir);
+    export_schema(&schema_for!(TypeLockResponse), &out_dir);
+    export_schema(&schema_for!(ConstraintsResponse), &out_dir);
+    export_schema(&schema_for!(SchemaResponse), &out_dir);
+    export_schema(&schema_for!(ListSchemasResponse), &out_dir);
+    export_schema(&schema_for!(StateRootResponse), &out_dir);
+    export_schema(&schema_for!(GetValueWithProofResponse), &out_dir);
+    export_schema(&schema_for!(ChangesSinceResponse), &out_dir);
+    export_schema(&schema_for!(ListSnapshotsResponse), &out_dir);
+    export_schema(&schema_for!(DiffSnapshotResponse), &out_dir);
+    export_schema(&schema_for!(RestoreSnapshotResponse), &out_dir);
+    export_schema(&schema_for!(ArithmeticResponse), &out_dir);
+    export_schema(&schema_for!(BoundsResponse), &out_dir);
+    export_schema(&schema_for!(ListByTagResponse), &out_dir);
+    export_schema(&schema_for!(ListChildrenResponse), &out_dir);
+    export_schema(&schema_for!(ListValuesResponse), &out_dir);
+    export_schema(&schema_for!(FindKeysByValueResponse), &out_dir);
+    export_schema(&schema_for!(AggregateResponse), &out_dir);
+    export_schema(&schema_for!(TopNResponse), &out_dir);
+    export_schema(&schema_for!(RankOfResponse), &out_dir);
+    export_schema(&schema_for!(VecContainsResponse), &out_dir);
+    export_schema(&schema_for!(VecSliceResponse), &out_dir);
+    export_schema(&schema_for!(VecLengthResponse), &out_dir);
 }
@@ -1,64 +1,177 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult,
+    to_binary, Addr, Binary, Deps, DepsMut, Empty, Env, Event, MessageInfo, Order, OverflowError,
+    Response, StdError, StdResult, Storage, Uint128,
 };
 use cw2::set_contract_version;
+use cw_storage_plus::{Bound, PrimaryKey, U64Key};
+use std::any::type_name;
 
 use crate::error::ContractError;
-use crate::msg::{ConfigResponse, ExecuteMsg, GetValueResponse, InstantiateMsg, QueryMsg};
-use crate::state::{Config, Primitive, CONFIG, DATA, DEFAULT_KEY};
+use crate::json_schema;
+use crate::merkle;
+use crate::msg::{
+    AggregateOp, AggregateResponse, ArithmeticResponse, BoundsResponse, ChangesSinceResponse,
+    ConfigResponse, ConstraintsResponse, DiffSnapshotResponse, ExecuteMsg, ExportEntry,
+    ExportMetadataResponse, ExportStateResponse, FindKeysByValueResponse, GetValueResponse,
+    GetValueWithProofResponse, InstantiateMsg, KeyInfoResponse, KeyScope, ListByTagResponse,
+    ListChildrenResponse, ListSchemasResponse, ListSnapshotsResponse, ListValuesResponse, Metadata,
+    MetadataCursor, MetadataKind, MigrateMsg, Op, OrderBy, QueryMsg, RankOfResponse, RankedValue,
+    RestoreSnapshotResponse, SchemaResponse, SnapshotCursor, SnapshotDiff, StateRootResponse,
+    TopNResponse, TypeLockResponse, VecContainsResponse, VecLengthResponse, VecSliceResponse,
+};
+use crate::state::{
+    change_key, data, load_primitive, load_tags, log_change, parse_change_key, parse_rank_key,
+    parse_tag_key, rank_key, record_change, remove_primitive, resolve_primitive, save_primitive,
+    tags, Bounds, Change, ChangeOp, Config, Constraint, KeyInfo, KeyRules, KeySchema, Primitive,
+    PrimitiveType, Snapshot, StoredVec, TypeLock, BOUNDS, CHANGE_LOG, CHANGE_SEQUENCE, CONFIG,
+    CONSTRAINTS, DEFAULT_KEY, KEY_INFO, MAX_DECIMALS, MAX_TAGS, PATH_SEPARATOR, PREFIX_CONSTRAINTS,
+    PREFIX_TYPE_LOCKS, RANKED_PREFIXES, RANK_BACKFILLS, RANK_INDEX, SCHEMAS, SNAPSHOTS,
+    SNAPSHOT_CHANGED_KEYS, SNAPSHOT_CHANGES, SNAPSHOT_COUNT, SNAPSHOT_LABELS, SYSTEM_PREFIX,
+    TYPE_LOCKS, UNIQUE_KEYS, VALUE_KEYS, VEC_CHUNK_THRESHOLD, VEC_LENGTHS,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:primitive-contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// settings for pagination
+const MAX_LIMIT: u32 = 100;
+const DEFAULT_LIMIT: u32 = 10;
+// RankOf counts the entries above a value, so it gives up on values ranked lower than this
+const MAX_RANK_SCAN: usize = 10_000;
+// aggregates only read values, so they can scan further per page
+const MAX_AGGREGATE_LIMIT: u32 = 500;
+const DEFAULT_AGGREGATE_LIMIT: u32 = 100;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    _msg: InstantiateMsg,
+    msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
+    let owner = match msg.owner {
+        Some(owner) => deps.api.addr_validate(&owner)?,
+        None => info.sender.clone(),
+    };
     let config = Config {
-        owner: info.sender.clone(),
+        owner: owner.clone(),
+        reverse_index: msg.reverse_index.unwrap_or(false),
+        key_rules: msg.key_rules.unwrap_or_default(),
+        default_key: msg.default_key.unwrap_or_else(|| DEFAULT_KEY.to_string()),
+        limits: msg.limits.unwrap_or_default(),
+        import_finished: false,
+        change_retention: msg.change_retention,
     };
+    check_config(&config)?;
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
     CONFIG.save(deps.storage, &config)?;
 
+    // Written after the config so that they go through the same checks as SetValue.
+    let values = msg.values.unwrap_or_default();
+    for initial in &values {
+        let name = &writable_name(deps.storage, &initial.name)?;
+        set_value(deps.storage, &env, &info.sender, name, &initial.value)?;
+    }
+
     Ok(Response::new()
         .add_attribute("method", "instantiate")
-        .add_attribute("owner", info.sender))
+        .add_attribute("owner", owner)
+        .add_attribute("values", values.len().to_string()))
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::SetValue { name, value } => execute_set_value(deps, info, name, value),
-        ExecuteMsg::DeleteValue { name } => execute_delete_value(deps, info, name),
+        ExecuteMsg::SetValue { name, value } => execute_set_value(deps, env, info, name, value),
+        ExecuteMsg::DeleteValue { name } => execute_delete_value(deps, env, info, name),
+        ExecuteMsg::Batch { ops } => execute_batch(deps, env, info, ops),
+        ExecuteMsg::Increment { name } => {
+            execute_add(deps, env, info, "increment", name, Uint128::new(1))
+        }
+        ExecuteMsg::Decrement { name } => {
+            execute_sub(deps, env, info, "decrement", name, Uint128::new(1))
+        }
+        ExecuteMsg::Add { name, amount } => execute_add(deps, env, info, "add", name, amount),
+        ExecuteMsg::Sub { name, amount } => execute_sub(deps, env, info, "sub", name, amount),
+        ExecuteMsg::VecPush { name, value } => execute_vec_push(deps, env, info, name, value),
+        ExecuteMsg::VecPop { name } => execute_vec_pop(deps, env, info, name),
+        ExecuteMsg::VecInsert { name, index, value } => {
+            execute_vec_insert(deps, env, info, name, index, value)
+        }
+        ExecuteMsg::VecRemove { name, index } => execute_vec_remove(deps, env, info, name, index),
+        ExecuteMsg::VecSet { name, index, value } => {
+            execute_vec_set(deps, env, info, name, index, value)
+        }
+        ExecuteMsg::SetAdd { name, value } => execute_set_add(deps, env, info, name, value),
+        ExecuteMsg::SetRemove { name, value } => execute_set_remove(deps, env, info, name, value),
+        ExecuteMsg::SetUnique { name, unique } => execute_set_unique(deps, info, name, unique),
+        ExecuteMsg::DeletePrefix { path } => execute_delete_prefix(deps, env, info, path),
+        ExecuteMsg::RebuildIndexes { start_after, limit } => {
+            execute_rebuild_indexes(deps, info, start_after, limit)
+        }
+        ExecuteMsg::ChunkVectors { start_after, limit } => {
+            execute_chunk_vectors(deps, info, start_after, limit)
+        }
+        ExecuteMsg::SetBounds { name, min, max } => execute_set_bounds(deps, info, name, min, max),
+        ExecuteMsg::SetRanked { path, ranked } => execute_set_ranked(deps, info, path, ranked),
+        ExecuteMsg::SetKeyInfo {
+            name,
+            description,
+            unit,
+            decimals,
+        } => execute_set_key_info(deps, info, name, description, unit, decimals),
+        ExecuteMsg::SetTypeLock { scope, lock } => execute_set_type_lock(deps, info, scope, lock),
+        ExecuteMsg::SetConstraints { scope, constraints } => {
+            execute_set_constraints(deps, info, scope, constraints)
+        }
+        ExecuteMsg::RegisterSchema {
+            name_or_prefix,
+            schema,
+        } => execute_register_schema(deps, info, name_or_prefix, schema),
+        ExecuteMsg::RemoveSchema { name_or_prefix } => {
+            execute_remove_schema(deps, info, name_or_prefix)
+        }
+        ExecuteMsg::SetKeyRules { rules } => execute_set_key_rules(deps, info, rules),
+        ExecuteMsg::ImportConfig { config } => execute_import_config(deps, info, config),
+        ExecuteMsg::ImportMetadata { metadata } => execute_import_metadata(deps, info, metadata),
+        ExecuteMsg::ImportState { entries } => execute_import_state(deps, env, info, entries),
+        ExecuteMsg::FinishImport {} => execute_finish_import(deps, info),
+        ExecuteMsg::SetTags { name, tags } => execute_set_tags(deps, info, name, tags),
+        ExecuteMsg::RemoveTags { name, tags } => execute_remove_tags(deps, info, name, tags),
+        ExecuteMsg::CreateSnapshot { label } => execute_create_snapshot(deps, env, info, label),
+        ExecuteMsg::DeleteSnapshot { label, limit } => {
+            execute_delete_snapshot(deps, info, label, limit)
+        }
+        ExecuteMsg::RestoreSnapshot {
+            label,
+            start_after,
+            limit,
+        } => execute_restore_snapshot(deps, env, info, label, start_after, limit),
+        ExecuteMsg::SetChangeRetention { blocks } => {
+            execute_set_change_retention(deps, info, blocks)
+        }
+        ExecuteMsg::PruneChanges { limit } => execute_prune_changes(deps, env, info, limit),
     }
 }
 
 pub fn execute_set_value(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     name: Option<String>,
     value: Primitive,
 ) -> Result<Response, ContractError> {
     check_is_owner(&deps, &info.sender)?;
-    if value.is_invalid() {
-        return Err(ContractError::InvalidPrimitive {});
-    }
-    let name: &str = get_name_or_default(&name);
-    DATA.update::<_, StdError>(deps.storage, &name, |old| match old {
-        Some(_) => Ok(value.clone()),
-        None => Ok(value.clone()),
-    })?;
+    let name = &writable_name(deps.storage, &name)?;
+    set_value(deps.storage, &env, &info.sender, name, &value)?;
 
     Ok(Response::new()
         .add_attribute("method", "set_value")
@@ -69,331 +182,7309 @@ pub fn execute_set_value(
 
 pub fn execute_delete_value(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     name: Option<String>,
 ) -> Result<Response, ContractError> {
     check_is_owner(&deps, &info.sender)?;
-    let name = get_name_or_default(&name);
-    DATA.remove(deps.storage, name);
+    let name = &get_name_or_default(deps.storage, &name)?;
+    check_not_reserved(name)?;
+    remove_value(deps.storage, &env, &info.sender, name)?;
     Ok(Response::new()
         .add_attribute("method", "delete_value")
         .add_attribute("sender", info.sender)
         .add_attribute("name", name))
 }
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::Config {} => to_binary(&query_config(deps)?),
-        QueryMsg::GetValue { name } => to_binary(&query_value(deps, name)?),
+pub fn execute_batch(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    ops: Vec<Op>,
+) -> Result<Response, ContractError> {
+    // Every op currently requires ownership so a single check covers the whole batch.
+    check_is_owner(&deps, &info.sender)?;
+    if ops.is_empty() {
+        return Err(ContractError::EmptyBatch {});
+    }
+    let max = CONFIG.load(deps.storage)?.limits.max_batch_ops as usize;
+    if ops.len() > max {
+        return Err(ContractError::BatchTooLarge { max });
+    }
+
+    // Returning an error from any op reverts the writes of the ops before it, so the batch is
+    // applied all or nothing.
+    let mut events: Vec<Event> = Vec::with_capacity(ops.len());
+    for (index, op) in ops.iter().enumerate() {
+        let event = Event::new("batch_op").add_attribute("index", index.to_string());
+        let event = match op {
+            Op::Set { name, value } => {
+                let name = &writable_name(deps.storage, name)?;
+                set_value(deps.storage, &env, &info.sender, name, value)?;
+                event
+                    .add_attribute("op", "set")
+                    .add_attribute("name", name)
+                    .add_attribute("value", format!("{:?}", value))
+            }
+            Op::Delete { name } => {
+                let name = &get_name_or_default(deps.storage, name)?;
+                check_not_reserved(name)?;
+                remove_value(deps.storage, &env, &info.sender, name)?;
+                event
+                    .add_attribute("op", "delete")
+                    .add_attribute("name", name)
+            }
+            Op::SetIf {
+                name,
+                expected,
+                value,
+            } => {
+                let name = &writable_name(deps.storage, name)?;
+                if &load_primitive(deps.storage, name)? != expected {
+                    return Err(ContractError::ConditionFailed {
+                        name: name.to_string(),
+                    });
+                }
+                set_value(deps.storage, &env, &info.sender, name, value)?;
+                event
+                    .add_attribute("op", "set_if")
+                    .add_attribute("name", name)
+                    .add_attribute("value", format!("{:?}", value))
+            }
+        };
+        events.push(event);
     }
+
+    Ok(Response::new()
+        .add_attribute("method", "batch")
+        .add_attribute("sender", info.sender)
+        .add_attribute("ops", ops.len().to_string())
+        .add_events(events))
 }
 
-fn query_value(deps: Deps, name: Option<String>) -> StdResult<GetValueResponse> {
-    let name = get_name_or_default(&name);
-    let value = DATA.load(deps.storage, name)?;
-    Ok(GetValueResponse {
-        name: name.to_string(),
-        value,
+pub fn execute_add(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    method: &str,
+    name: Option<String>,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    update_uint128(deps, env, info, method, name, |value| {
+        value.checked_add(amount)
     })
 }
 
-fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
-    let config = CONFIG.load(deps.storage)?;
-    Ok(ConfigResponse {
-        owner: config.owner,
+pub fn execute_sub(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    method: &str,
+    name: Option<String>,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    update_uint128(deps, env, info, method, name, |value| {
+        value.checked_sub(amount)
     })
 }
 
-fn get_name_or_default(name: &Option<String>) -> &str {
-    match name {
-        None => DEFAULT_KEY,
-        Some(s) => &s,
-    }
+fn update_uint128<F>(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    method: &str,
+    name: Option<String>,
+    action: F,
+) -> Result<Response, ContractError>
+where
+    F: FnOnce(Uint128) -> Result<Uint128, OverflowError>,
+{
+    check_is_owner(&deps, &info.sender)?;
+    let name = &writable_name(deps.storage, &name)?;
+    let current = match load_primitive(deps.storage, name)? {
+        Some(primitive) => primitive.try_get_uint128()?,
+        None => Uint128::zero(),
+    };
+    let value = action(current).map_err(StdError::from)?;
+    set_value(
+        deps.storage,
+        &env,
+        &info.sender,
+        name,
+        &Primitive::Uint128(value),
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("method", method)
+        .add_attribute("sender", info.sender)
+        .add_attribute("name", name)
+        .add_attribute("value", value)
+        .set_data(to_binary(&ArithmeticResponse {
+            name: name.to_string(),
+            value,
+        })?))
 }
 
-fn check_is_owner(deps: &DepsMut, address: &Addr) -> Result<bool, ContractError> {
-    let config = CONFIG.load(deps.storage)?;
-    if &config.owner != address {
-        return Err(ContractError::Unauthorized {});
-    }
-    Ok(true)
+pub fn execute_vec_push(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: Option<String>,
+    value: Primitive,
+) -> Result<Response, ContractError> {
+    update_vec(
+        deps,
+        env,
+        info,
+        "vec_push",
+        name,
+        None,
+        |storage, vector| {
+            check_element(storage, vector, &value, None)?;
+            vector.push(storage, value.clone())?;
+            Ok(value)
+        },
+    )
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use cosmwasm_std::from_binary;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+pub fn execute_vec_pop(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: Option<String>,
+) -> Result<Response, ContractError> {
+    update_vec(deps, env, info, "vec_pop", name, None, |storage, vector| {
+        vector.pop(storage)?.ok_or(ContractError::EmptyVector {})
+    })
+}
 
-    fn query_value_helper(deps: Deps, name: Option<String>) -> GetValueResponse {
-        from_binary(&query(deps, mock_env(), QueryMsg::GetValue { name }).unwrap()).unwrap()
-    }
+pub fn execute_vec_insert(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: Option<String>,
+    index: u32,
+    value: Primitive,
+) -> Result<Response, ContractError> {
+    update_vec(
+        deps,
+        env,
+        info,
+        "vec_insert",
+        name,
+        Some(index),
+        |storage, vector| {
+            // Inserting at the end is allowed, so the index may equal the length.
+            if index > vector.len() {
+                return Err(index_out_of_range(index, vector));
+            }
+            check_element(storage, vector, &value, None)?;
+            vector.insert(storage, index, value.clone())?;
+            Ok(value)
+        },
+    )
+}
 
-    #[test]
-    fn proper_initialization() {
-        let mut deps = mock_dependencies(&[]);
+pub fn execute_vec_remove(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: Option<String>,
+    index: u32,
+) -> Result<Response, ContractError> {
+    update_vec(
+        deps,
+        env,
+        info,
+        "vec_remove",
+        name,
+        Some(index),
+        |storage, vector| {
+            if index >= vector.len() {
+                return Err(index_out_of_range(index, vector));
+            }
+            Ok(vector.remove(storage, index)?)
+        },
+    )
+}
 
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &[]);
+pub fn execute_vec_set(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: Option<String>,
+    index: u32,
+    value: Primitive,
+) -> Result<Response, ContractError> {
+    update_vec(
+        deps,
+        env,
+        info,
+        "vec_set",
+        name,
+        Some(index),
+        |storage, vector| {
+            if index >= vector.len() {
+                return Err(index_out_of_range(index, vector));
+            }
+            check_element(storage, vector, &value, Some(index))?;
+            vector.set(storage, index, value.clone())?;
+            Ok(value)
+        },
+    )
+}
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
-    }
+/// Loads the Vec stored under name, applies action to it and stores the result. The action
+/// returns the element it added, replaced with or removed, which is reported back as the value.
+fn update_vec<F>(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    method: &str,
+    name: Option<String>,
+    index: Option<u32>,
+    action: F,
+) -> Result<Response, ContractError>
+where
+    F: FnOnce(&mut dyn Storage, &mut StoredVec) -> Result<Primitive, ContractError>,
+{
+    check_is_owner(&deps, &info.sender)?;
+    let name = &writable_name(deps.storage, &name)?;
+    // Chunked Vecs are changed by the action itself.
+    record_change(deps.storage, name)?;
+    let mut vector = StoredVec::load(deps.storage, name)?;
+    let value = action(deps.storage, &mut vector)?;
+    let hash = vector.value_hash(deps.storage)?;
+    vector.save(deps.storage)?;
+    vec_changed(deps.storage, &env, &info.sender, name, hash)?;
 
-    #[test]
-    fn set_and_update_value_with_name() {
-        let mut deps = mock_dependencies(&[]);
+    let mut response = Response::new()
+        .add_attribute("method", method)
+        .add_attribute("sender", info.sender)
+        .add_attribute("name", name);
+    if let Some(index) = index {
+        response = response.add_attribute("index", index.to_string());
+    }
+    Ok(response.add_attribute("value", format!("{:?}", value)))
+}
 
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &[]);
+pub fn execute_set_add(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: Option<String>,
+    value: Primitive,
+) -> Result<Response, ContractError> {
+    update_set(
+        deps,
+        env,
+        info,
+        "set_add",
+        name,
+        value,
+        |storage, vector, value| {
+            if vector.contains(storage, value)? {
+                return Ok(false);
+            }
+            check_element(storage, vector, value, None)?;
+            vector.push(storage, value.clone())?;
+            Ok(true)
+        },
+    )
+}
 
-        // we can just call .unwrap() to assert this was a success
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+pub fn execute_set_remove(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    name: Option<String>,
+    value: Primitive,
+) -> Result<Response, ContractError> {
+    update_set(
+        deps,
+        env,
+        info,
+        "set_remove",
+        name,
+        value,
+        |storage, vector, value| match vector.position(storage, value)? {
+            Some(index) => {
+                vector.remove(storage, index)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        },
+    )
+}
 
-        let msg = ExecuteMsg::SetValue {
-            name: Some("test1".to_string()),
-            value: Primitive::String("value1".to_string()),
-        };
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-        assert_eq!(
-            Response::new()
-                .add_attribute("method", "set_value")
-                .add_attribute("sender", "creator")
-                .add_attribute("name", "test1")
-                .add_attribute("value", "String(\"value1\")"),
-            res
-        );
+/// Like update_vec, but the action reports whether it changed the Vec and nothing is stored
+/// when it did not.
+fn update_set<F>(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    method: &str,
+    name: Option<String>,
+    value: Primitive,
+    action: F,
+) -> Result<Response, ContractError>
+where
+    F: FnOnce(&mut dyn Storage, &mut StoredVec, &Primitive) -> Result<bool, ContractError>,
+{
+    check_is_owner(&deps, &info.sender)?;
+    let name = &writable_name(deps.storage, &name)?;
+    record_change(deps.storage, name)?;
+    let mut vector = StoredVec::load(deps.storage, name)?;
+    let changed = action(deps.storage, &mut vector, &value)?;
+    if changed {
+        let hash = vector.value_hash(deps.storage)?;
+        vector.save(deps.storage)?;
+        vec_changed(deps.storage, &env, &info.sender, name, hash)?;
+    }
 
-        let query_res: GetValueResponse =
-            query_value_helper(deps.as_ref(), Some("test1".to_string()));
+    Ok(Response::new()
+        .add_attribute("method", method)
+        .add_attribute("sender", info.sender)
+        .add_attribute("name", name)
+        .add_attribute("value", format!("{:?}", value))
+        .add_attribute("changed", changed.to_string()))
+}
 
-        assert_eq!(
-            GetValueResponse {
-                name: "test1".to_string(),
-                value: Primitive::String("value1".to_string())
-            },
-            query_res
-        );
+/// Validates a single element written into a stored Vec. Checking elements one at a time avoids
+/// loading chunked Vecs in full. replacing is the index of the element being overwritten, if any.
+fn check_element(
+    storage: &dyn Storage,
+    vector: &StoredVec,
+    value: &Primitive,
+    replacing: Option<u32>,
+) -> Result<(), ContractError> {
+    // Same rule as Primitive::is_invalid, vectors cannot be nested.
+    if matches!(value, Primitive::Vec(_)) {
+        return Err(ContractError::InvalidPrimitive {});
+    }
+    for lock in type_locks(storage, vector.name())? {
+        if let Some(got) = lock.element_mismatch(value) {
+            return Err(ContractError::TypeMismatch {
+                expected: lock.describe(),
+                got,
+            });
+        }
+    }
+    for constraint in constraints(storage, vector.name())? {
+        check_constraint(vector.name(), &constraint, value)?;
+        // Only an element that is not replacing another one makes the Vec longer.
+        if replacing.is_none() {
+            check_len_constraint(vector.name(), &constraint, vector.len() + 1)?;
+        }
+    }
+    for schema in schemas(storage, vector.name())? {
+        if let Some(lock) = schema.type_lock() {
+            if let Some(got) = lock.element_mismatch(value) {
+                return Err(ContractError::TypeMismatch {
+                    expected: lock.describe(),
+                    got,
+                });
+            }
+        }
+        for constraint in schema.item_constraints() {
+            check_constraint(vector.name(), &constraint, value)?;
+        }
+    }
+    if UNIQUE_KEYS.has(storage, vector.name()) {
+        if let Some(index) = vector.position(storage, value)? {
+            if Some(index) != replacing {
+                return Err(ContractError::DuplicateElement {
+                    name: vector.name().to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
 
-        // Update the value to something else
-        let msg = ExecuteMsg::SetValue {
-            name: Some("test1".to_string()),
-            value: Primitive::String("value2".to_string()),
-        };
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+pub fn execute_set_unique(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: Option<String>,
+    unique: bool,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let name = &writable_name(deps.storage, &name)?;
+    if unique {
+        // The value already stored has to satisfy the flag.
+        if let Some(value) = load_primitive(deps.storage, name)? {
+            check_unique(name, &value)?;
+        }
+        UNIQUE_KEYS.save(deps.storage, name, &true)?;
+    } else {
+        UNIQUE_KEYS.remove(deps.storage, name);
+    }
 
-        let query_res: GetValueResponse =
-            query_value_helper(deps.as_ref(), Some("test1".to_string()));
+    Ok(Response::new()
+        .add_attribute("method", "set_unique")
+        .add_attribute("sender", info.sender)
+        .add_attribute("name", name)
+        .add_attribute("unique", unique.to_string()))
+}
 
-        assert_eq!(
-            GetValueResponse {
-                name: "test1".to_string(),
-                value: Primitive::String("value2".to_string())
-            },
-            query_res
-        );
+fn index_out_of_range(index: u32, vector: &StoredVec) -> ContractError {
+    ContractError::IndexOutOfRange {
+        index,
+        len: vector.len(),
     }
+}
 
-    #[test]
-    fn set_and_update_value_without_name() {
-        let mut deps = mock_dependencies(&[]);
+pub fn execute_delete_prefix(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    path: String,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let max_deletions = CONFIG.load(deps.storage)?.limits.max_prefix_deletions as usize;
+    let prefix = dir_prefix(&path);
+    // Clearing the whole store is almost certainly a mistake.
+    if prefix.is_empty() {
+        return Err(ContractError::InvalidPath { path });
+    }
+    check_not_reserved(&prefix)?;
+    let (keys, done) = take_page(
+        data().prefix(()).keys(
+            deps.storage,
+            Some(Bound::inclusive(prefix.as_str())),
+            prefix_end(prefix.as_bytes()).map(Bound::exclusive),
+            Order::Ascending,
+        ),
+        max_deletions,
+    );
+    for key in &keys {
+        let name = std::str::from_utf8(key).map_err(StdError::from)?;
+        remove_value(deps.storage, &env, &info.sender, name)?;
+    }
+    let deleted = keys.len();
 
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &[]);
+    Ok(Response::new()
+        .add_attribute("method", "delete_prefix")
+        .add_attribute("sender", info.sender)
+        .add_attribute("path", path)
+        .add_attribute("deleted", deleted.to_string())
+        .add_attribute("done", done.to_string()))
+}
 
-        // we can just call .unwrap() to assert this was a success
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+/// Adds the index entries of values stored before the indexes existed. Scans at most limit keys
+/// after start_after so large stores can be migrated over several transactions.
+pub fn execute_rebuild_indexes(
+    deps: DepsMut,
+    info: MessageInfo,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.as_deref().map(Bound::exclusive);
+    let entries: Vec<(Vec<u8>, Primitive)> = data()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
 
-        let msg = ExecuteMsg::SetValue {
-            name: None,
-            value: Primitive::String("value1".to_string()),
-        };
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-        assert_eq!(
-            Response::new()
-                .add_attribute("method", "set_value")
-                .add_attribute("sender", "creator")
-                .add_attribute("name", DEFAULT_KEY)
-                .add_attribute("value", "String(\"value1\")"),
-            res
-        );
+    for (key, value) in &entries {
+        let name = std::str::from_utf8(key).map_err(StdError::from)?;
+        // Without an old value nothing is removed, existing index entries are just rewritten.
+        data().replace(deps.storage, name, Some(value), None)?;
+        update_value_keys(deps.storage, name, None, Some(value))?;
+        update_rank_index(deps.storage, name, None, Some(value))?;
+        let value = resolve_primitive(deps.storage, name, value.clone())?;
+        // Chunked Vecs written before the Merkle tree existed have no element tree yet.
+        if let Primitive::Vec(elements) = &value {
+            if let Some(len) = VEC_LENGTHS.may_load(deps.storage, name)? {
+                merkle::set_elements(deps.storage, name, len, elements)?;
+            }
+        }
+        merkle::insert(deps.storage, name, &value)?;
+    }
 
-        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+    // Without a cursor the page reached the end of the store.
+    let next_start_after = next_cursor(&entries, limit, |(key, _)| {
+        Ok(String::from_utf8(key.clone())?)
+    })?;
+    advance_rank_backfills(
+        deps.storage,
+        start_after.as_deref(),
+        next_start_after.as_deref().map(str::as_bytes),
+    )?;
 
-        assert_eq!(
-            GetValueResponse {
-                name: DEFAULT_KEY.to_string(),
-                value: Primitive::String("value1".to_string())
-            },
-            query_res
-        );
+    let mut response = Response::new()
+        .add_attribute("method", "rebuild_indexes")
+        .add_attribute("sender", info.sender)
+        .add_attribute("indexed", entries.len().to_string());
+    if let Some(key) = next_start_after {
+        response = response.add_attribute("next_start_after", key);
+    }
+    Ok(response)
+}
 
-        // Update the value to something else
-        let msg = ExecuteMsg::SetValue {
-            name: None,
-            value: Primitive::String("value2".to_string()),
+/// Records that RebuildIndexes indexed every key after start_after up to end, or up to the end
+/// of the store, and drops the backfills of the ranked prefixes that are complete now.
+fn advance_rank_backfills(
+    storage: &mut dyn Storage,
+    start_after: Option<&str>,
+    end: Option<&[u8]>,
+) -> StdResult<()> {
+    let backfills = RANK_BACKFILLS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(Vec<u8>, Option<String>)>>>()?;
+    for (prefix, indexed_through) in backfills {
+        let prefix = String::from_utf8(prefix)?;
+        // The page only continues a backfill if no key of the prefix was skipped before it.
+        let continues = match (start_after, &indexed_through) {
+            (None, _) => true,
+            (Some(start_after), None) => start_after < prefix.as_str(),
+            (Some(start_after), Some(through)) => start_after <= through.as_str(),
         };
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        if !continues {
+            continue;
+        }
+        let complete = match (end, prefix_end(prefix.as_bytes())) {
+            (None, _) => true,
+            (Some(end), Some(prefix_end)) => end >= prefix_end.as_slice(),
+            (Some(_), None) => false,
+        };
+        if complete {
+            RANK_BACKFILLS.remove(storage, &prefix);
+        } else if let Some(end) = end.filter(|end| *end >= prefix.as_bytes()) {
+            let end = String::from_utf8(end.to_vec())?;
+            let through = match indexed_through {
+                Some(through) if through > end => through,
+                _ => end,
+            };
+            RANK_BACKFILLS.save(storage, &prefix, &Some(through))?;
+        }
+    }
+    Ok(())
+}
 
-        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+/// Moves Vecs that were stored as a single blob before chunking existed, or that were never
+/// rewritten since, to the chunked layout. Scans at most limit keys after start_after so large
+/// stores can be migrated over several transactions.
+pub fn execute_chunk_vectors(
+    deps: DepsMut,
+    info: MessageInfo,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let entries: Vec<(Vec<u8>, Primitive)> = data()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
 
-        assert_eq!(
-            GetValueResponse {
-                name: DEFAULT_KEY.to_string(),
-                value: Primitive::String("value2".to_string())
-            },
-            query_res
-        );
+    let mut converted = 0;
+    for (key, value) in &entries {
+        let name = String::from_utf8(key.clone()).map_err(StdError::from)?;
+        if let Primitive::Vec(vector) = value {
+            if vector.len() > VEC_CHUNK_THRESHOLD as usize && !VEC_LENGTHS.has(deps.storage, &name)
+            {
+                save_primitive(deps.storage, &name, value)?;
+                converted += 1;
+            }
+        }
     }
 
-    #[test]
-    fn cannot_set_nested_vector_primitive() {
-        let mut deps = mock_dependencies(&[]);
-
-        let msg = InstantiateMsg {};
-        let info = mock_info("creator", &[]);
+    let next_start_after = next_cursor(&entries, limit, |(key, _)| {
+        Ok(String::from_utf8(key.clone())?)
+    })?;
+    let mut response = Response::new()
+        .add_attribute("method", "chunk_vectors")
+        .add_attribute("sender", info.sender)
+        .add_attribute("converted", converted.to_string());
+    if let Some(key) = next_start_after {
+        response = response.add_attribute("next_start_after", key);
+    }
+    Ok(response)
+}
+
+pub fn execute_set_bounds(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: Option<String>,
+    min: Option<Uint128>,
+    max: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let name = &writable_name(deps.storage, &name)?;
+    if min.is_none() && max.is_none() {
+        BOUNDS.remove(deps.storage, name);
+    } else {
+        let bounds = Bounds { min, max };
+        if !bounds.is_valid() {
+            return Err(ContractError::InvalidBounds {});
+        }
+        // The value already stored has to satisfy the new bounds.
+        if let Some(Primitive::Uint128(value)) = load_primitive(deps.storage, name)? {
+            check_bounds(name, &bounds, value)?;
+        }
+        BOUNDS.save(deps.storage, name, &bounds)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "set_bounds")
+        .add_attribute("sender", info.sender)
+        .add_attribute("name", name)
+        .add_attribute("min", format!("{:?}", min))
+        .add_attribute("max", format!("{:?}", max)))
+}
+
+pub fn execute_set_ranked(
+    deps: DepsMut,
+    info: MessageInfo,
+    path: String,
+    ranked: bool,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let max_deletions = CONFIG.load(deps.storage)?.limits.max_prefix_deletions as usize;
+    let prefix = dir_prefix(&path);
+    let mut response = Response::new()
+        .add_attribute("method", "set_ranked")
+        .add_attribute("sender", info.sender)
+        .add_attribute("path", path.clone())
+        .add_attribute("ranked", ranked.to_string());
+
+    if ranked {
+        enable_rank_index(deps.storage, &path)?;
+        return Ok(response);
+    }
+
+    RANKED_PREFIXES.remove(deps.storage, &prefix);
+    RANK_BACKFILLS.remove(deps.storage, &prefix);
+    let (entries, done) = take_page(
+        RANK_INDEX
+            .prefix(&prefix)
+            .keys(deps.storage, None, None, Order::Ascending),
+        max_deletions,
+    );
+    for entry in &entries {
+        RANK_INDEX.remove(deps.storage, (&prefix, entry));
+    }
+    response = response
+        .add_attribute("deleted", entries.len().to_string())
+        .add_attribute("done", done.to_string());
+    Ok(response)
+}
+
+fn enable_rank_index(storage: &mut dyn Storage, path: &str) -> Result<(), ContractError> {
+    let prefix = dir_prefix(path);
+    // Entries left behind by an unfinished disable would point at outdated values.
+    if !RANKED_PREFIXES.has(storage, &prefix)
+        && RANK_INDEX
+            .prefix(&prefix)
+            .keys(storage, None, None, Order::Ascending)
+            .next()
+            .is_some()
+    {
+        return Err(ContractError::IndexNotCleared {
+            path: path.to_string(),
+        });
+    }
+    if RANKED_PREFIXES.has(storage, &prefix) {
+        return Ok(());
+    }
+    RANKED_PREFIXES.save(storage, &prefix, &Empty {})?;
+    // Keys stored so far are only indexed by RebuildIndexes.
+    if data()
+        .prefix(())
+        .keys(
+            storage,
+            Some(Bound::inclusive(prefix.as_str())),
+            prefix_end(prefix.as_bytes()).map(Bound::exclusive),
+            Order::Ascending,
+        )
+        .next()
+        .is_some()
+    {
+        RANK_BACKFILLS.save(storage, &prefix, &None)?;
+    }
+    Ok(())
+}
+
+pub fn execute_set_key_info(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: Option<String>,
+    description: Option<String>,
+    unit: Option<String>,
+    decimals: Option<u8>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let name = &writable_name(deps.storage, &name)?;
+    check_exists(deps.storage, name)?;
+    let key_info = KeyInfo {
+        description,
+        unit,
+        decimals,
+    };
+    check_key_info(&key_info)?;
+    if key_info == KeyInfo::default() {
+        KEY_INFO.remove(deps.storage, name);
+    } else {
+        KEY_INFO.save(deps.storage, name, &key_info)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "set_key_info")
+        .add_attribute("sender", info.sender)
+        .add_attribute("name", name))
+}
+
+pub fn execute_set_type_lock(
+    deps: DepsMut,
+    info: MessageInfo,
+    scope: KeyScope,
+    lock: Option<TypeLock>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let (key, is_prefix) = writable_scope_key(deps.storage, &scope)?;
+    set_type_lock(deps.storage, &key, is_prefix, lock.as_ref())?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_type_lock")
+        .add_attribute("sender", info.sender)
+        .add_attribute(if is_prefix { "path" } else { "name" }, key)
+        .add_attribute(
+            "lock",
+            lock.map_or_else(|| "none".to_string(), |lock| lock.describe()),
+        ))
+}
+
+fn set_type_lock(
+    storage: &mut dyn Storage,
+    key: &str,
+    is_prefix: bool,
+    lock: Option<&TypeLock>,
+) -> Result<(), ContractError> {
+    let locks = match is_prefix {
+        true => PREFIX_TYPE_LOCKS,
+        false => TYPE_LOCKS,
+    };
+    match lock {
+        Some(lock) => {
+            if !lock.is_valid() {
+                return Err(ContractError::InvalidTypeLock {});
+            }
+            // The value already stored has to satisfy the new lock.
+            if !is_prefix {
+                if let Some(value) = load_primitive(storage, key)? {
+                    check_type(lock, &value)?;
+                }
+            }
+            locks.save(storage, key, lock)?;
+        }
+        None => locks.remove(storage, key),
+    }
+    Ok(())
+}
+
+pub fn execute_set_constraints(
+    deps: DepsMut,
+    info: MessageInfo,
+    scope: KeyScope,
+    constraints: Vec<Constraint>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let (key, is_prefix) = writable_scope_key(deps.storage, &scope)?;
+    set_constraints(deps.storage, &key, is_prefix, &constraints)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_constraints")
+        .add_attribute("sender", info.sender)
+        .add_attribute(if is_prefix { "path" } else { "name" }, key)
+        .add_attribute("constraints", constraints.len().to_string()))
+}
+
+fn set_constraints(
+    storage: &mut dyn Storage,
+    key: &str,
+    is_prefix: bool,
+    constraints: &[Constraint],
+) -> Result<(), ContractError> {
+    for constraint in constraints {
+        match constraint {
+            Constraint::Range {
+                min: Some(min),
+                max: Some(max),
+            } if min > max => return Err(ContractError::InvalidBounds {}),
+            Constraint::OneOf { values } if values.iter().any(|v| !v.is_scalar()) => {
+                return Err(ContractError::InvalidPrimitive {})
+            }
+            _ => {}
+        }
+    }
+    let stored = match is_prefix {
+        true => PREFIX_CONSTRAINTS,
+        false => CONSTRAINTS,
+    };
+    if constraints.is_empty() {
+        stored.remove(storage, key);
+    } else {
+        // The value already stored has to satisfy the new constraints.
+        if !is_prefix {
+            if let Some(value) = load_primitive(storage, key)? {
+                check_constraints(key, constraints, &value)?;
+            }
+        }
+        stored.save(storage, key, &constraints.to_vec())?;
+    }
+    Ok(())
+}
+
+pub fn execute_register_schema(
+    deps: DepsMut,
+    info: MessageInfo,
+    name_or_prefix: String,
+    schema: String,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    // A prefix has to be a valid name once its trailing separator is dropped.
+    let name = name_or_prefix
+        .strip_suffix(PATH_SEPARATOR)
+        .unwrap_or(&name_or_prefix);
+    check_writable(deps.storage, name)?;
+    register_schema(deps.storage, &name_or_prefix, &schema)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "register_schema")
+        .add_attribute("sender", info.sender)
+        .add_attribute("name_or_prefix", name_or_prefix))
+}
+
+fn register_schema(
+    storage: &mut dyn Storage,
+    name_or_prefix: &str,
+    document: &str,
+) -> Result<(), ContractError> {
+    let schema =
+        json_schema::parse(document).map_err(|reason| ContractError::InvalidSchema { reason })?;
+    if let Some(reason) = schema.invalid_reason() {
+        return Err(ContractError::InvalidSchema {
+            reason: reason.to_string(),
+        });
+    }
+    // The value already stored has to satisfy the new schema.
+    if !name_or_prefix.ends_with(PATH_SEPARATOR) {
+        if let Some(value) = load_primitive(storage, name_or_prefix)? {
+            check_schema(name_or_prefix, &schema, &value)?;
+        }
+    }
+    SCHEMAS.save(storage, name_or_prefix, &schema)?;
+    Ok(())
+}
+
+pub fn execute_remove_schema(
+    deps: DepsMut,
+    info: MessageInfo,
+    name_or_prefix: String,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    SCHEMAS.remove(deps.storage, &name_or_prefix);
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_schema")
+        .add_attribute("sender", info.sender)
+        .add_attribute("name_or_prefix", name_or_prefix))
+}
+
+pub fn execute_set_key_rules(
+    deps: DepsMut,
+    info: MessageInfo,
+    rules: KeyRules,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let mut config = CONFIG.load(deps.storage)?;
+    // Writes without a name would all fail if the default key broke the new rules.
+    if let Some(reason) = rules.invalid_reason(&config.default_key) {
+        return Err(ContractError::InvalidKey { reason });
+    }
+    config.key_rules = rules.clone();
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "set_key_rules")
+        .add_attribute("sender", info.sender)
+        .add_attribute("max_length", rules.max_length.to_string())
+        .add_attribute(
+            "allowed_symbols",
+            rules.allowed_symbols.unwrap_or_else(|| "any".to_string()),
+        ))
+}
+
+pub fn execute_import_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    imported: ConfigResponse,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    check_importable(deps.storage, 0)?;
+    let mut config = CONFIG.load(deps.storage)?;
+    // VALUE_KEYS would be missing the values stored so far, or keep outdated entries.
+    if imported.reverse_index != config.reverse_index
+        && data()
+            .range(deps.storage, None, None, Order::Ascending)
+            .next()
+            .is_some()
+    {
+        return Err(ContractError::StoreNotEmpty {});
+    }
+    config.reverse_index = imported.reverse_index;
+    config.key_rules = imported.key_rules;
+    config.default_key = imported.default_key;
+    config.limits = imported.limits;
+    config.change_retention = imported.change_retention;
+    check_config(&config)?;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "import_config")
+        .add_attribute("sender", info.sender))
+}
+
+pub fn execute_import_metadata(
+    deps: DepsMut,
+    info: MessageInfo,
+    metadata: Vec<Metadata>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    check_importable(deps.storage, metadata.len())?;
+
+    // As for values, names are only checked against the system prefix.
+    for item in &metadata {
+        match item {
+            Metadata::TypeLock { scope, lock } => {
+                let (key, is_prefix) = scope_key(deps.storage, scope)?;
+                check_not_reserved(&key)?;
+                set_type_lock(deps.storage, &key, is_prefix, Some(lock))?;
+            }
+            Metadata::Constraints { scope, constraints } => {
+                let (key, is_prefix) = scope_key(deps.storage, scope)?;
+                check_not_reserved(&key)?;
+                set_constraints(deps.storage, &key, is_prefix, constraints)?;
+            }
+            Metadata::Schema {
+                name_or_prefix,
+                schema,
+            } => {
+                check_not_reserved(name_or_prefix)?;
+                register_schema(deps.storage, name_or_prefix, schema)?;
+            }
+            Metadata::Ranked { path } => enable_rank_index(deps.storage, path)?,
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "import_metadata")
+        .add_attribute("sender", info.sender)
+        .add_attribute("imported", metadata.len().to_string()))
+}
+
+/// Checks that imports are still open and that count items fit into one call.
+fn check_importable(storage: &dyn Storage, count: usize) -> Result<(), ContractError> {
+    let config = CONFIG.load(storage)?;
+    if config.import_finished {
+        return Err(ContractError::ImportFinished {});
+    }
+    let max = config.limits.max_batch_ops as usize;
+    if count > max {
+        return Err(ContractError::BatchTooLarge { max });
+    }
+    Ok(())
+}
+
+pub fn execute_import_state(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    entries: Vec<ExportEntry>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    check_importable(deps.storage, entries.len())?;
+
+    for entry in &entries {
+        // The key rules are not applied so that every key of the exported instance can be copied.
+        check_not_reserved(&entry.name)?;
+        let name = entry.name.as_str();
+        if let Some(bounds) = &entry.bounds {
+            if !bounds.is_valid() {
+                return Err(ContractError::InvalidBounds {});
+            }
+            if let Primitive::Uint128(value) = entry.value {
+                check_bounds(name, bounds, value)?;
+            }
+        }
+        if entry.unique {
+            check_unique(name, &entry.value)?;
+        }
+        if let Some(key_info) = &entry.info {
+            check_key_info(key_info)?;
+        }
+        let mut entry_tags = entry.tags.clone();
+        entry_tags.sort();
+        entry_tags.dedup();
+        check_tags(name, &entry_tags)?;
+        // Metadata restricting the value is replaced after it, so that it is checked against the
+        // metadata of the exported instance rather than that of the key being replaced.
+        BOUNDS.remove(deps.storage, name);
+        UNIQUE_KEYS.remove(deps.storage, name);
+        set_value(deps.storage, &env, &info.sender, name, &entry.value)?;
+        if let Some(bounds) = &entry.bounds {
+            BOUNDS.save(deps.storage, name, bounds)?;
+        }
+        if entry.unique {
+            UNIQUE_KEYS.save(deps.storage, name, &true)?;
+        }
+        match &entry.info {
+            Some(key_info) => KEY_INFO.save(deps.storage, name, key_info)?,
+            None => KEY_INFO.remove(deps.storage, name),
+        }
+        for tag in load_tags(deps.storage, name)? {
+            tags().remove(deps.storage, (name, &tag))?;
+        }
+        for tag in &entry_tags {
+            tags().save(deps.storage, (name, tag), tag)?;
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "import_state")
+        .add_attribute("sender", info.sender)
+        .add_attribute("imported", entries.len().to_string()))
+}
+
+pub fn execute_finish_import(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config.import_finished = true;
+        Ok(config)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("method", "finish_import")
+        .add_attribute("sender", info.sender))
+}
+
+pub fn execute_create_snapshot(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    label: String,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    if SNAPSHOT_LABELS.has(deps.storage, &label) {
+        return Err(ContractError::SnapshotExists { label });
+    }
+    let id = SNAPSHOT_COUNT.may_load(deps.storage)?.unwrap_or_default() + 1;
+    let snapshot = Snapshot {
+        id,
+        label: label.clone(),
+        height: env.block.height,
+        time: env.block.time,
+    };
+    SNAPSHOTS.save(deps.storage, U64Key::new(id), &snapshot)?;
+    SNAPSHOT_LABELS.save(deps.storage, &label, &id)?;
+    SNAPSHOT_COUNT.save(deps.storage, &id)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "create_snapshot")
+        .add_attribute("sender", info.sender)
+        .add_attribute("label", label)
+        .add_attribute("id", id.to_string()))
+}
+
+pub fn execute_delete_snapshot(
+    deps: DepsMut,
+    info: MessageInfo,
+    label: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let oldest = |storage: &dyn Storage| -> StdResult<Option<Snapshot>> {
+        SNAPSHOTS
+            .range(storage, None, None, Order::Ascending)
+            .next()
+            .transpose()
+            .map(|item| item.map(|(_, snapshot)| snapshot))
+    };
+    let mut response = Response::new()
+        .add_attribute("method", "delete_snapshot")
+        .add_attribute("sender", info.sender);
+
+    if let Some(label) = label {
+        let id = SNAPSHOT_LABELS.load(deps.storage, &label)?;
+        if oldest(deps.storage)?.map(|snapshot| snapshot.id) != Some(id) {
+            return Err(ContractError::NotOldestSnapshot { label });
+        }
+        SNAPSHOTS.remove(deps.storage, U64Key::new(id));
+        SNAPSHOT_LABELS.remove(deps.storage, &label);
+        response = response.add_attribute("label", label);
+    }
+
+    // Changes recorded for deleted snapshots sort before those of the oldest one left.
+    let end = oldest(deps.storage)?.map(|snapshot| Bound::exclusive(change_key(snapshot.id, "")));
+    let (keys, done) = take_page(
+        SNAPSHOT_CHANGES.keys(deps.storage, None, end, Order::Ascending),
+        limit,
+    );
+    for key in &keys {
+        let (id, name) = parse_change_key(key)?;
+        SNAPSHOT_CHANGES.remove(deps.storage, (U64Key::new(id), &name));
+        SNAPSHOT_CHANGED_KEYS.remove(deps.storage, (&name, U64Key::new(id)));
+    }
+
+    Ok(response
+        .add_attribute("deleted", keys.len().to_string())
+        .add_attribute("done", done.to_string()))
+}
+
+pub fn execute_restore_snapshot(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    label: String,
+    start_after: Option<SnapshotCursor>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let id = SNAPSHOT_LABELS.load(deps.storage, &label)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    // Changes are undone from the latest to the oldest, so that the value recorded for the first
+    // change after the snapshot is the one left in place. Writes made here are recorded for the
+    // latest snapshot, whose changes are always undone first, so they are never visited.
+    let min = Bound::inclusive(change_key(id, ""));
+    let max = start_after.map(|cursor| Bound::exclusive(change_key(cursor.snapshot, &cursor.name)));
+    let entries: Vec<(Vec<u8>, Option<Primitive>)> = SNAPSHOT_CHANGES
+        .range(deps.storage, Some(min), max, Order::Descending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+
+    for (key, value) in &entries {
+        let (_, name) = parse_change_key(key)?;
+        match value {
+            Some(value) => set_value(deps.storage, &env, &info.sender, &name, value)?,
+            None => remove_value(deps.storage, &env, &info.sender, &name)?,
+        }
+    }
+    let next_start_after = next_cursor(&entries, limit, |(key, _)| snapshot_cursor(key))?;
+
+    Ok(Response::new()
+        .add_attribute("method", "restore_snapshot")
+        .add_attribute("sender", info.sender)
+        .add_attribute("label", label.clone())
+        .add_attribute("restored", entries.len().to_string())
+        .add_attribute("done", next_start_after.is_none().to_string())
+        .set_data(to_binary(&RestoreSnapshotResponse {
+            label,
+            restored: entries.len() as u32,
+            next_start_after,
+        })?))
+}
+
+pub fn execute_set_change_retention(
+    deps: DepsMut,
+    info: MessageInfo,
+    blocks: Option<u64>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    CONFIG.update(deps.storage, |mut config| -> StdResult<_> {
+        config.change_retention = blocks;
+        Ok(config)
+    })?;
+
+    let blocks = blocks.map_or_else(|| "none".to_string(), |blocks| blocks.to_string());
+    Ok(Response::new()
+        .add_attribute("method", "set_change_retention")
+        .add_attribute("sender", info.sender)
+        .add_attribute("blocks", blocks))
+}
+
+pub fn execute_prune_changes(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let retention = CONFIG.load(deps.storage)?.change_retention;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    // Entries are ordered by height as well, so pruning stops at the first one to keep.
+    let expired: Vec<Change> = match retention {
+        Some(blocks) => {
+            let cutoff = env.block.height.saturating_sub(blocks);
+            CHANGE_LOG
+                .range(deps.storage, None, None, Order::Ascending)
+                .take(limit)
+                .map(|item| item.map(|(_, change)| change))
+                .take_while(|change| {
+                    change
+                        .as_ref()
+                        .map_or(true, |change| change.height < cutoff)
+                })
+                .collect::<StdResult<_>>()?
+        }
+        None => vec![],
+    };
+    for change in &expired {
+        CHANGE_LOG.remove(deps.storage, U64Key::new(change.sequence));
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "prune_changes")
+        .add_attribute("sender", info.sender)
+        .add_attribute("pruned", expired.len().to_string())
+        .add_attribute("done", (expired.len() < limit).to_string()))
+}
+
+pub fn execute_set_tags(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: Option<String>,
+    new_tags: Vec<String>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let name = &writable_name(deps.storage, &name)?;
+    check_exists(deps.storage, name)?;
+    let mut current = load_tags(deps.storage, name)?;
+    let mut added = vec![];
+    for tag in &new_tags {
+        if !current.contains(tag) {
+            current.push(tag.clone());
+            added.push(tag);
+        }
+    }
+    check_tags(name, &current)?;
+    for tag in added {
+        tags().save(deps.storage, (name, tag), tag)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "set_tags")
+        .add_attribute("sender", info.sender)
+        .add_attribute("name", name)
+        .add_attribute("tags", new_tags.join(",")))
+}
+
+pub fn execute_remove_tags(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: Option<String>,
+    old_tags: Vec<String>,
+) -> Result<Response, ContractError> {
+    check_is_owner(&deps, &info.sender)?;
+    let name = &get_name_or_default(deps.storage, &name)?;
+    for tag in &old_tags {
+        tags().remove(deps.storage, (name, tag))?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "remove_tags")
+        .add_attribute("sender", info.sender)
+        .add_attribute("name", name)
+        .add_attribute("tags", old_tags.join(",")))
+}
+
+/// Metadata can only be attached to keys holding a value, as it is removed with them.
+fn check_exists(storage: &dyn Storage, name: &str) -> Result<(), ContractError> {
+    match data().may_load(storage, name)? {
+        Some(_) => Ok(()),
+        None => Err(StdError::not_found(type_name::<Primitive>()).into()),
+    }
+}
+
+fn check_unique(name: &str, value: &Primitive) -> Result<(), ContractError> {
+    if value.has_duplicates() {
+        return Err(ContractError::DuplicateElement {
+            name: name.to_string(),
+        });
+    }
+    Ok(())
+}
+
+fn check_key_info(key_info: &KeyInfo) -> Result<(), ContractError> {
+    if matches!(key_info.decimals, Some(decimals) if decimals > MAX_DECIMALS) {
+        return Err(ContractError::InvalidDecimals { max: MAX_DECIMALS });
+    }
+    Ok(())
+}
+
+/// Checks the full list of distinct tags name would have.
+fn check_tags(name: &str, all_tags: &[String]) -> Result<(), ContractError> {
+    if all_tags.iter().any(|tag| tag.is_empty()) {
+        return Err(ContractError::InvalidTag {});
+    }
+    if all_tags.len() > MAX_TAGS {
+        return Err(ContractError::TooManyTags {
+            name: name.to_string(),
+            max: MAX_TAGS,
+        });
+    }
+    Ok(())
+}
+
+/// Validates and stores value under name. writer is recorded in the change log.
+fn set_value(
+    storage: &mut dyn Storage,
+    env: &Env,
+    writer: &Addr,
+    name: &str,
+    value: &Primitive,
+) -> Result<(), ContractError> {
+    if value.is_invalid() {
+        return Err(ContractError::InvalidPrimitive {});
+    }
+    for lock in type_locks(storage, name)? {
+        check_type(&lock, value)?;
+    }
+    check_constraints(name, &constraints(storage, name)?, value)?;
+    for schema in schemas(storage, name)? {
+        check_schema(name, &schema, value)?;
+    }
+    if let Primitive::Uint128(value) = value {
+        if let Some(bounds) = BOUNDS.may_load(storage, name)? {
+            check_bounds(name, &bounds, *value)?;
+        }
+    }
+    if value.has_duplicates() && UNIQUE_KEYS.has(storage, name) {
+        return Err(ContractError::DuplicateElement {
+            name: name.to_string(),
+        });
+    }
+    record_change(storage, name)?;
+    let old = data().may_load(storage, name)?;
+    update_value_keys(storage, name, old.as_ref(), Some(value))?;
+    update_rank_index(storage, name, old.as_ref(), Some(value))?;
+    save_primitive(storage, name, value)?;
+    merkle::insert(storage, name, value)?;
+    log_change(storage, env.block.height, writer, name, ChangeOp::Set)?;
+    Ok(())
+}
+
+/// Does what set_value does after storing a value for a Vec changed in place.
+fn vec_changed(
+    storage: &mut dyn Storage,
+    env: &Env,
+    writer: &Addr,
+    name: &str,
+    hash: merkle::Hash,
+) -> StdResult<()> {
+    merkle::insert_hash(storage, name, hash)?;
+    log_change(storage, env.block.height, writer, name, ChangeOp::Set)
+}
+
+fn remove_value(storage: &mut dyn Storage, env: &Env, writer: &Addr, name: &str) -> StdResult<()> {
+    record_change(storage, name)?;
+    let old = data().may_load(storage, name)?;
+    if old.is_some() {
+        merkle::remove(storage, name)?;
+        log_change(storage, env.block.height, writer, name, ChangeOp::Delete)?;
+    }
+    update_value_keys(storage, name, old.as_ref(), None)?;
+    update_rank_index(storage, name, old.as_ref(), None)?;
+    remove_primitive(storage, name)?;
+    BOUNDS.remove(storage, name);
+    UNIQUE_KEYS.remove(storage, name);
+    KEY_INFO.remove(storage, name);
+    for tag in load_tags(storage, name)? {
+        tags().remove(storage, (name, &tag))?;
+    }
+    Ok(())
+}
+
+/// Moves name from the reverse index entry of its old value to that of its new one. Vecs are not
+/// indexed, so a placeholder of a chunked Vec can be passed as old.
+fn update_value_keys(
+    storage: &mut dyn Storage,
+    name: &str,
+    old: Option<&Primitive>,
+    new: Option<&Primitive>,
+) -> StdResult<()> {
+    if !CONFIG.load(storage)?.reverse_index {
+        return Ok(());
+    }
+    if let Some(old) = old.filter(|p| p.is_scalar()) {
+        VALUE_KEYS.remove(storage, (&old.hash()?, name));
+    }
+    if let Some(new) = new.filter(|p| p.is_scalar()) {
+        VALUE_KEYS.save(storage, (&new.hash()?, name), &Empty {})?;
+    }
+    Ok(())
+}
+
+/// Moves name within the rank index of every ranked prefix it is below. Only Uint128 values are
+/// ranked.
+fn update_rank_index(
+    storage: &mut dyn Storage,
+    name: &str,
+    old: Option<&Primitive>,
+    new: Option<&Primitive>,
+) -> StdResult<()> {
+    for prefix in ranked_prefixes(storage, name) {
+        if let Some(Primitive::Uint128(old)) = old {
+            RANK_INDEX.remove(storage, (&prefix, &rank_key(*old, name)));
+        }
+        if let Some(Primitive::Uint128(new)) = new {
+            RANK_INDEX.save(storage, (&prefix, &rank_key(*new, name)), &Empty {})?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the ranked prefixes name is below, from the root to the closest one.
+fn ranked_prefixes(storage: &dyn Storage, name: &str) -> Vec<String> {
+    enclosing_prefixes(name)
+        .filter(|prefix| RANKED_PREFIXES.has(storage, prefix))
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the directory prefixes name is below, from the root to the closest one.
+fn enclosing_prefixes(name: &str) -> impl Iterator<Item = &str> {
+    std::iter::once(0)
+        .chain(
+            name.match_indices(PATH_SEPARATOR)
+                .map(|(i, _)| i + 1)
+                .filter(move |end| *end < name.len()),
+        )
+        .map(move |end| &name[..end])
+}
+
+/// Returns the storage key of scope and whether it is a prefix.
+fn scope_key(storage: &dyn Storage, scope: &KeyScope) -> StdResult<(String, bool)> {
+    match scope {
+        KeyScope::Key { name } => Ok((get_name_or_default(storage, name)?, false)),
+        KeyScope::Prefix { path } => Ok((dir_prefix(path), true)),
+    }
+}
+
+/// Like scope_key, after checking that callers may write to the key, or below the prefix.
+fn writable_scope_key(
+    storage: &dyn Storage,
+    scope: &KeyScope,
+) -> Result<(String, bool), ContractError> {
+    let (key, is_prefix) = scope_key(storage, scope)?;
+    match is_prefix {
+        true => check_not_reserved(&key)?,
+        false => check_writable(storage, &key)?,
+    }
+    Ok((key, is_prefix))
+}
+
+/// Returns the type lock of name and those of every prefix it is below.
+fn type_locks(storage: &dyn Storage, name: &str) -> StdResult<Vec<TypeLock>> {
+    let mut locks = vec![];
+    for prefix in enclosing_prefixes(name) {
+        if let Some(lock) = PREFIX_TYPE_LOCKS.may_load(storage, prefix)? {
+            locks.push(lock);
+        }
+    }
+    if let Some(lock) = TYPE_LOCKS.may_load(storage, name)? {
+        locks.push(lock);
+    }
+    Ok(locks)
+}
+
+/// Returns the constraints of every prefix name is below and those of name itself.
+fn constraints(storage: &dyn Storage, name: &str) -> StdResult<Vec<Constraint>> {
+    let mut constraints = vec![];
+    for prefix in enclosing_prefixes(name) {
+        if let Some(found) = PREFIX_CONSTRAINTS.may_load(storage, prefix)? {
+            constraints.extend(found);
+        }
+    }
+    if let Some(found) = CONSTRAINTS.may_load(storage, name)? {
+        constraints.extend(found);
+    }
+    Ok(constraints)
+}
+
+/// Returns the schemas registered for every prefix name starts with and for name itself.
+fn schemas(storage: &dyn Storage, name: &str) -> StdResult<Vec<KeySchema>> {
+    let mut schemas = vec![];
+    // The empty prefix cannot be told apart from a key without a name, schemas need a path.
+    for prefix in enclosing_prefixes(name).filter(|prefix| !prefix.is_empty()) {
+        if let Some(schema) = SCHEMAS.may_load(storage, prefix)? {
+            schemas.push(schema);
+        }
+    }
+    if let Some(schema) = SCHEMAS.may_load(storage, name)? {
+        schemas.push(schema);
+    }
+    Ok(schemas)
+}
+
+fn check_schema(name: &str, schema: &KeySchema, value: &Primitive) -> Result<(), ContractError> {
+    if let Some(lock) = schema.type_lock() {
+        check_type(&lock, value)?;
+    }
+    match value {
+        Primitive::Vec(vector) => {
+            // The enum of a schema without a type only lists single values, so no Vec equals
+            // one of them.
+            if schema.r#enum.is_some() {
+                return Err(ContractError::NotAllowed {
+                    name: name.to_string(),
+                });
+            }
+            for constraint in schema.item_constraints() {
+                for element in vector {
+                    check_constraint(name, &constraint, element)?;
+                }
+            }
+        }
+        _ => {
+            for constraint in schema.constraints() {
+                check_constraint(name, &constraint, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_constraints(
+    name: &str,
+    constraints: &[Constraint],
+    value: &Primitive,
+) -> Result<(), ContractError> {
+    for constraint in constraints {
+        match value {
+            Primitive::Vec(vector) => {
+                check_len_constraint(name, constraint, vector.len() as u32)?;
+                for element in vector {
+                    check_constraint(name, constraint, element)?;
+                }
+            }
+            _ => check_constraint(name, constraint, value)?,
+        }
+    }
+    Ok(())
+}
+
+/// Checks a single value, or element of a Vec, against constraint.
+fn check_constraint(
+    name: &str,
+    constraint: &Constraint,
+    value: &Primitive,
+) -> Result<(), ContractError> {
+    if constraint.allows(value) {
+        return Ok(());
+    }
+    let name = name.to_string();
+    Err(match (constraint, value) {
+        (Constraint::Range { .. }, Primitive::Uint128(value)) => ContractError::OutOfBounds {
+            name,
+            value: *value,
+        },
+        (Constraint::MaxLength { max }, _) => ContractError::TooLong { name, max: *max },
+        (Constraint::MaxChars { max }, _) => ContractError::TooManyChars { name, max: *max },
+        (Constraint::Pattern { pattern }, _) => ContractError::PatternMismatch {
+            name,
+            pattern: pattern.clone(),
+        },
+        _ => ContractError::NotAllowed { name },
+    })
+}
+
+fn check_len_constraint(
+    name: &str,
+    constraint: &Constraint,
+    len: u32,
+) -> Result<(), ContractError> {
+    match constraint {
+        Constraint::MaxItems { max } if !constraint.allows_len(len) => {
+            Err(ContractError::TooManyItems {
+                name: name.to_string(),
+                max: *max,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_type(lock: &TypeLock, value: &Primitive) -> Result<(), ContractError> {
+    match lock.mismatch(value) {
+        Some(got) => Err(ContractError::TypeMismatch {
+            expected: lock.describe(),
+            got,
+        }),
+        None => Ok(()),
+    }
+}
+
+fn check_bounds(name: &str, bounds: &Bounds, value: Uint128) -> Result<(), ContractError> {
+    if !bounds.contains(value) {
+        return Err(ContractError::OutOfBounds {
+            name: name.to_string(),
+            value,
+        });
+    }
+    Ok(())
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("version", CONTRACT_VERSION))
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::GetValue { name, with_info } => {
+            to_binary(&query_value(deps, name, with_info.unwrap_or(false))?)
+        }
+        QueryMsg::KeyInfo { name } => to_binary(&query_key_info(deps, name)?),
+        QueryMsg::VecContains { name, value } => to_binary(&query_vec_contains(deps, name, value)?),
+        QueryMsg::GetVecSlice {
+            name,
+            offset,
+            limit,
+        } => to_binary(&query_vec_slice(deps, name, offset, limit)?),
+        QueryMsg::GetVecLength { name } => to_binary(&query_vec_length(deps, name)?),
+        QueryMsg::ListChildren {
+            path,
+            start_after,
+            limit,
+        } => to_binary(&query_list_children(deps, path, start_after, limit)?),
+        QueryMsg::ListByPrefix {
+            prefix,
+            start_after,
+            limit,
+        } => to_binary(&query_list_by_prefix(deps, prefix, start_after, limit)?),
+        QueryMsg::ListRange {
+            start,
+            end,
+            order,
+            limit,
+        } => to_binary(&query_list_range(deps, start, end, order, limit)?),
+        QueryMsg::ListByType {
+            r#type,
+            start_after,
+            limit,
+        } => to_binary(&query_list_by_type(deps, r#type, start_after, limit)?),
+        QueryMsg::FindKeysByValue {
+            value,
+            start_after,
+            limit,
+        } => to_binary(&query_find_keys_by_value(deps, value, start_after, limit)?),
+        QueryMsg::GetBounds { name } => to_binary(&query_bounds(deps, name)?),
+        QueryMsg::TopN {
+            prefix,
+            limit,
+            order,
+        } => to_binary(&query_top_n(deps, prefix, limit, order)?),
+        QueryMsg::GetTypeLock { scope } => to_binary(&query_type_lock(deps, scope)?),
+        QueryMsg::GetConstraints { scope } => to_binary(&query_constraints(deps, scope)?),
+        QueryMsg::GetSchema { name_or_prefix } => to_binary(&query_schema(deps, name_or_prefix)?),
+        QueryMsg::ListSchemas { start_after, limit } => {
+            to_binary(&query_list_schemas(deps, start_after, limit)?)
+        }
+        QueryMsg::ExportState { start_after, limit } => {
+            to_binary(&query_export_state(deps, start_after, limit)?)
+        }
+        QueryMsg::ExportMetadata { start_after, limit } => {
+            to_binary(&query_export_metadata(deps, start_after, limit)?)
+        }
+        QueryMsg::ListByTag {
+            tag,
+            start_after,
+            limit,
+        } => to_binary(&query_list_by_tag(deps, tag, start_after, limit)?),
+        QueryMsg::Aggregate {
+            prefix,
+            op,
+            start_after,
+            limit,
+        } => to_binary(&query_aggregate(deps, prefix, op, start_after, limit)?),
+        QueryMsg::RankOf { name } => to_binary(&query_rank_of(deps, name)?),
+        QueryMsg::ListSnapshots { start_after, limit } => {
+            to_binary(&query_list_snapshots(deps, start_after, limit)?)
+        }
+        QueryMsg::DiffSnapshot {
+            label,
+            start_after,
+            limit,
+        } => to_binary(&query_diff_snapshot(deps, label, start_after, limit)?),
+        QueryMsg::ChangesSince { sequence, limit } => {
+            to_binary(&query_changes_since(deps, sequence, limit)?)
+        }
+        QueryMsg::StateRoot {} => to_binary(&query_state_root(deps)?),
+        QueryMsg::GetValueWithProof { name } => to_binary(&query_value_with_proof(deps, name)?),
+    }
+}
+
+fn query_value(deps: Deps, name: Option<String>, with_info: bool) -> StdResult<GetValueResponse> {
+    let name = &get_name_or_default(deps.storage, &name)?;
+    let value = load_primitive(deps.storage, name)?
+        .ok_or_else(|| StdError::not_found(type_name::<Primitive>()))?;
+    let info = match with_info {
+        true => Some(KEY_INFO.may_load(deps.storage, name)?.unwrap_or_default()),
+        false => None,
+    };
+    Ok(GetValueResponse {
+        name: name.to_string(),
+        value,
+        tags: load_tags(deps.storage, name)?,
+        info,
+    })
+}
+
+fn query_key_info(deps: Deps, name: Option<String>) -> StdResult<KeyInfoResponse> {
+    let name = &get_name_or_default(deps.storage, &name)?;
+    let info = KEY_INFO.may_load(deps.storage, name)?.unwrap_or_default();
+    Ok(KeyInfoResponse {
+        name: name.to_string(),
+        description: info.description,
+        unit: info.unit,
+        decimals: info.decimals,
+    })
+}
+
+fn query_vec_contains(
+    deps: Deps,
+    name: Option<String>,
+    value: Primitive,
+) -> StdResult<VecContainsResponse> {
+    let name = &get_name_or_default(deps.storage, &name)?;
+    let vector = StoredVec::load(deps.storage, name)?;
+    Ok(VecContainsResponse {
+        name: name.to_string(),
+        contains: vector.contains(deps.storage, &value)?,
+    })
+}
+
+fn query_vec_slice(
+    deps: Deps,
+    name: Option<String>,
+    offset: Option<u32>,
+    limit: Option<u32>,
+) -> StdResult<VecSliceResponse> {
+    let name = &get_name_or_default(deps.storage, &name)?;
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    let vector = StoredVec::load(deps.storage, name)?;
+    Ok(VecSliceResponse {
+        name: name.to_string(),
+        offset,
+        values: vector.slice(deps.storage, offset, limit)?,
+        length: vector.len(),
+    })
+}
+
+fn query_vec_length(deps: Deps, name: Option<String>) -> StdResult<VecLengthResponse> {
+    let name = &get_name_or_default(deps.storage, &name)?;
+    let vector = StoredVec::load(deps.storage, name)?;
+    Ok(VecLengthResponse {
+        name: name.to_string(),
+        length: vector.len(),
+    })
+}
+
+fn query_list_children(
+    deps: Deps,
+    path: Option<String>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListChildrenResponse> {
+    let path = path.unwrap_or_default();
+    let prefix = dir_prefix(&path);
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let end = prefix_end(prefix.as_bytes()).map(Bound::exclusive);
+    let mut start = match start_after {
+        Some(child) => Some(child_bound(&prefix, &child)),
+        None => Some(Bound::inclusive(prefix.as_str())),
+    };
+
+    let mut children = vec![];
+    'seek: while children.len() < limit {
+        let keys =
+            data()
+                .prefix(())
+                .keys(deps.storage, start.take(), end.clone(), Order::Ascending);
+        for key in keys {
+            let child = String::from_utf8(key[prefix.len()..].to_vec())?;
+            if let Some(index) = child.find(PATH_SEPARATOR) {
+                // Report the sub-directory once and continue after everything inside it.
+                let directory = format!("{}{}", &child[..index], PATH_SEPARATOR);
+                start = Some(child_bound(&prefix, &directory));
+                children.push(directory);
+                continue 'seek;
+            }
+            children.push(child);
+            if children.len() == limit {
+                break;
+            }
+        }
+        break;
+    }
+
+    Ok(ListChildrenResponse { path, children })
+}
+
+fn query_list_by_prefix(
+    deps: Deps,
+    prefix: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListValuesResponse> {
+    let start = match start_after {
+        Some(name) if name > prefix => Bound::exclusive(name),
+        _ => Bound::inclusive(prefix.as_str()),
+    };
+    let end = prefix_end(prefix.as_bytes()).map(Bound::exclusive);
+    list_values(deps, Some(start), end, Order::Ascending, limit)
+}
+
+fn query_list_range(
+    deps: Deps,
+    start: Option<String>,
+    end: Option<String>,
+    order: Option<OrderBy>,
+    limit: Option<u32>,
+) -> StdResult<ListValuesResponse> {
+    let order = order.map_or(Order::Ascending, Order::from);
+    list_values(
+        deps,
+        start.map(Bound::inclusive),
+        end.map(Bound::exclusive),
+        order,
+        limit,
+    )
+}
+
+fn query_list_by_type(
+    deps: Deps,
+    primitive_type: PrimitiveType,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListValuesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let values = data()
+        .idx
+        .primitive_type
+        .prefix(primitive_type.as_str().as_bytes().to_vec())
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, value) = item?;
+            let name = String::from_utf8(key)?;
+            let value = resolve_primitive(deps.storage, &name, value)?;
+            let tags = load_tags(deps.storage, &name)?;
+            Ok(GetValueResponse {
+                name,
+                value,
+                tags,
+                info: None,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListValuesResponse { values })
+}
+
+fn query_type_lock(deps: Deps, scope: KeyScope) -> StdResult<TypeLockResponse> {
+    let lock = match scope_key(deps.storage, &scope)? {
+        (key, true) => PREFIX_TYPE_LOCKS.may_load(deps.storage, &key)?,
+        (key, false) => TYPE_LOCKS.may_load(deps.storage, &key)?,
+    };
+    Ok(TypeLockResponse { scope, lock })
+}
+
+fn query_constraints(deps: Deps, scope: KeyScope) -> StdResult<ConstraintsResponse> {
+    let constraints = match scope_key(deps.storage, &scope)? {
+        (key, true) => PREFIX_CONSTRAINTS.may_load(deps.storage, &key)?,
+        (key, false) => CONSTRAINTS.may_load(deps.storage, &key)?,
+    };
+    Ok(ConstraintsResponse {
+        scope,
+        constraints: constraints.unwrap_or_default(),
+    })
+}
+
+fn query_schema(deps: Deps, name_or_prefix: String) -> StdResult<SchemaResponse> {
+    let schema = SCHEMAS
+        .may_load(deps.storage, &name_or_prefix)?
+        .map(|schema| json_schema::to_string(&schema));
+    Ok(SchemaResponse {
+        name_or_prefix,
+        schema,
+    })
+}
+
+fn query_list_schemas(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListSchemasResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let schemas = SCHEMAS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, schema) = item?;
+            Ok(SchemaResponse {
+                name_or_prefix: String::from_utf8(key)?,
+                schema: Some(json_schema::to_string(&schema)),
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListSchemasResponse { schemas })
+}
+
+fn query_list_snapshots(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<ListSnapshotsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive_int);
+    let snapshots = SNAPSHOTS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, snapshot)| snapshot))
+        .collect::<StdResult<_>>()?;
+    Ok(ListSnapshotsResponse { snapshots })
+}
+
+fn snapshot_cursor(key: &[u8]) -> StdResult<SnapshotCursor> {
+    let (snapshot, name) = parse_change_key(key)?;
+    Ok(SnapshotCursor { snapshot, name })
+}
+
+fn query_diff_snapshot(
+    deps: Deps,
+    label: String,
+    start_after: Option<SnapshotCursor>,
+    limit: Option<u32>,
+) -> StdResult<DiffSnapshotResponse> {
+    let id = SNAPSHOT_LABELS.load(deps.storage, &label)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some(cursor) => Bound::exclusive(change_key(cursor.snapshot, &cursor.name)),
+        None => Bound::inclusive(change_key(id, "")),
+    };
+    let entries: Vec<(Vec<u8>, Option<Primitive>)> = SNAPSHOT_CHANGES
+        .range(deps.storage, Some(start), None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+
+    let mut changes = vec![];
+    for (key, snapshot_value) in &entries {
+        let (snapshot, name) = parse_change_key(key)?;
+        // Keys changed after several snapshots are listed for the first of them only, which
+        // recorded the value they held when this snapshot was taken.
+        let earlier = SNAPSHOT_CHANGED_KEYS
+            .prefix(&name)
+            .keys(
+                deps.storage,
+                Some(Bound::inclusive_int(id)),
+                Some(Bound::exclusive_int(snapshot)),
+                Order::Ascending,
+            )
+            .next();
+        if earlier.is_some() {
+            continue;
+        }
+        let current_value = load_primitive(deps.storage, &name)?;
+        if *snapshot_value != current_value {
+            changes.push(SnapshotDiff {
+                name,
+                snapshot_value: snapshot_value.clone(),
+                current_value,
+            });
+        }
+    }
+    let next_start_after = next_cursor(&entries, limit, |(key, _)| snapshot_cursor(key))?;
+    Ok(DiffSnapshotResponse {
+        label,
+        changes,
+        next_start_after,
+    })
+}
+
+fn query_changes_since(
+    deps: Deps,
+    sequence: u64,
+    limit: Option<u32>,
+) -> StdResult<ChangesSinceResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let changes = CHANGE_LOG
+        .range(
+            deps.storage,
+            Some(Bound::exclusive_int(sequence)),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|item| item.map(|(_, change)| change))
+        .collect::<StdResult<_>>()?;
+    Ok(ChangesSinceResponse {
+        changes,
+        latest_sequence: CHANGE_SEQUENCE.may_load(deps.storage)?.unwrap_or_default(),
+    })
+}
+
+fn query_state_root(deps: Deps) -> StdResult<StateRootResponse> {
+    Ok(StateRootResponse {
+        root: Binary::from(merkle::root(deps.storage)?),
+    })
+}
+
+fn query_value_with_proof(
+    deps: Deps,
+    name: Option<String>,
+) -> StdResult<GetValueWithProofResponse> {
+    let name = get_name_or_default(deps.storage, &name)?;
+    let value = load_primitive(deps.storage, &name)?
+        .ok_or_else(|| StdError::not_found(type_name::<Primitive>()))?;
+    // Values stored before the tree existed have no leaf until RebuildIndexes adds it.
+    let proof = merkle::prove(deps.storage, &name)?
+        .ok_or_else(|| StdError::generic_err(format!("No proof for {} yet", name)))?;
+    Ok(GetValueWithProofResponse {
+        name,
+        value,
+        proof: proof.into_iter().map(Binary::from).collect(),
+        root: Binary::from(merkle::root(deps.storage)?),
+    })
+}
+
+fn query_export_state(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ExportStateResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let entries: Vec<ExportEntry> = data()
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, value) = item?;
+            let name = String::from_utf8(key)?;
+            Ok(ExportEntry {
+                value: resolve_primitive(deps.storage, &name, value)?,
+                tags: load_tags(deps.storage, &name)?,
+                info: KEY_INFO.may_load(deps.storage, &name)?,
+                bounds: BOUNDS.may_load(deps.storage, &name)?,
+                unique: UNIQUE_KEYS.has(deps.storage, &name),
+                name,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+
+    let next_start_after = next_cursor(&entries, limit, |entry| Ok(entry.name.clone()))?;
+    Ok(ExportStateResponse {
+        config: query_config(deps)?,
+        entries,
+        next_start_after,
+    })
+}
+
+fn query_export_metadata(
+    deps: Deps,
+    start_after: Option<MetadataCursor>,
+    limit: Option<u32>,
+) -> StdResult<ExportMetadataResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let kinds = [
+        MetadataKind::TypeLock,
+        MetadataKind::PrefixTypeLock,
+        MetadataKind::Constraints,
+        MetadataKind::PrefixConstraints,
+        MetadataKind::Schema,
+        MetadataKind::Ranked,
+    ];
+    let mut metadata = vec![];
+    let mut cursors = vec![];
+    for kind in kinds.iter().copied() {
+        let start = match &start_after {
+            Some(cursor) if cursor.kind > kind => continue,
+            Some(cursor) if cursor.kind == kind => Some(Bound::exclusive(cursor.key.as_str())),
+            _ => None,
+        };
+        let remaining = limit - metadata.len();
+        if remaining == 0 {
+            break;
+        }
+        let keys: Vec<String> = match kind {
+            MetadataKind::TypeLock => TYPE_LOCKS.keys(deps.storage, start, None, Order::Ascending),
+            MetadataKind::PrefixTypeLock => {
+                PREFIX_TYPE_LOCKS.keys(deps.storage, start, None, Order::Ascending)
+            }
+            MetadataKind::Constraints => {
+                CONSTRAINTS.keys(deps.storage, start, None, Order::Ascending)
+            }
+            MetadataKind::PrefixConstraints => {
+                PREFIX_CONSTRAINTS.keys(deps.storage, start, None, Order::Ascending)
+            }
+            MetadataKind::Schema => SCHEMAS.keys(deps.storage, start, None, Order::Ascending),
+            MetadataKind::Ranked => {
+                RANKED_PREFIXES.keys(deps.storage, start, None, Order::Ascending)
+            }
+        }
+        .take(remaining)
+        .map(String::from_utf8)
+        .collect::<Result<_, _>>()?;
+        for key in keys {
+            metadata.push(load_metadata(deps.storage, kind, &key)?);
+            cursors.push(MetadataCursor { kind, key });
+        }
+    }
+
+    let next_start_after = next_cursor(&cursors, limit, |cursor| Ok(cursor.clone()))?;
+    Ok(ExportMetadataResponse {
+        metadata,
+        next_start_after,
+    })
+}
+
+fn load_metadata(storage: &dyn Storage, kind: MetadataKind, key: &str) -> StdResult<Metadata> {
+    let name = || KeyScope::Key {
+        name: Some(key.to_string()),
+    };
+    let path = || KeyScope::Prefix {
+        path: key.to_string(),
+    };
+    Ok(match kind {
+        MetadataKind::TypeLock => Metadata::TypeLock {
+            scope: name(),
+            lock: TYPE_LOCKS.load(storage, key)?,
+        },
+        MetadataKind::PrefixTypeLock => Metadata::TypeLock {
+            scope: path(),
+            lock: PREFIX_TYPE_LOCKS.load(storage, key)?,
+        },
+        MetadataKind::Constraints => Metadata::Constraints {
+            scope: name(),
+            constraints: CONSTRAINTS.load(storage, key)?,
+        },
+        MetadataKind::PrefixConstraints => Metadata::Constraints {
+            scope: path(),
+            constraints: PREFIX_CONSTRAINTS.load(storage, key)?,
+        },
+        MetadataKind::Schema => Metadata::Schema {
+            name_or_prefix: key.to_string(),
+            schema: json_schema::to_string(&SCHEMAS.load(storage, key)?),
+        },
+        MetadataKind::Ranked => Metadata::Ranked {
+            path: key.to_string(),
+        },
+    })
+}
+
+fn query_list_by_tag(
+    deps: Deps,
+    tag: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ListByTagResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start =
+        start_after.map(|name| Bound::exclusive((name.as_str(), tag.as_str()).joined_key()));
+    let keys = tags()
+        .idx
+        .tag
+        .prefix(tag.as_bytes().to_vec())
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|key| parse_tag_key(&key))
+        .collect::<StdResult<_>>()?;
+    Ok(ListByTagResponse { tag, keys })
+}
+
+fn query_aggregate(
+    deps: Deps,
+    prefix: String,
+    op: AggregateOp,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<AggregateResponse> {
+    let limit = limit
+        .unwrap_or(DEFAULT_AGGREGATE_LIMIT)
+        .min(MAX_AGGREGATE_LIMIT) as usize;
+    let start = match start_after {
+        Some(name) if name > prefix => Bound::exclusive(name),
+        _ => Bound::inclusive(prefix.as_str()),
+    };
+    let end = prefix_end(prefix.as_bytes()).map(Bound::exclusive);
+    // Only Uint128 values are read, other keys under the prefix are skipped by the type index.
+    let entries: Vec<(Vec<u8>, Primitive)> = data()
+        .idx
+        .primitive_type
+        .prefix(PrimitiveType::Uint128.as_str().as_bytes().to_vec())
+        .range(deps.storage, Some(start), end, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+
+    let mut value = match op {
+        AggregateOp::Sum | AggregateOp::Count => Some(Uint128::zero()),
+        AggregateOp::Min | AggregateOp::Max => None,
+    };
+    for (_, entry) in &entries {
+        let entry = entry.try_get_uint128()?;
+        value = Some(match (op, value) {
+            (AggregateOp::Sum, Some(total)) => total.checked_add(entry)?,
+            (AggregateOp::Count, Some(count)) => count + Uint128::new(1),
+            (AggregateOp::Min, Some(min)) => min.min(entry),
+            (AggregateOp::Max, Some(max)) => max.max(entry),
+            (_, None) => entry,
+        });
+    }
+
+    let next_start_after = next_cursor(&entries, limit, |(key, _)| {
+        Ok(String::from_utf8(key.clone())?)
+    })?;
+    Ok(AggregateResponse {
+        prefix,
+        op,
+        value,
+        count: entries.len() as u32,
+        next_start_after,
+    })
+}
+
+fn query_find_keys_by_value(
+    deps: Deps,
+    value: Primitive,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<FindKeysByValueResponse> {
+    if !CONFIG.load(deps.storage)?.reverse_index {
+        return Err(StdError::generic_err("Reverse index is not enabled"));
+    }
+    if !value.is_scalar() {
+        return Err(StdError::generic_err("Only scalar values are indexed"));
+    }
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let hash = value.hash()?;
+    let keys = VALUE_KEYS
+        .prefix(&hash)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(String::from_utf8)
+        .collect::<Result<_, _>>()?;
+    Ok(FindKeysByValueResponse { keys })
+}
+
+fn list_values(
+    deps: Deps,
+    min: Option<Bound>,
+    max: Option<Bound>,
+    order: Order,
+    limit: Option<u32>,
+) -> StdResult<ListValuesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let values = data()
+        .range(deps.storage, min, max, order)
+        .take(limit)
+        .map(|item| {
+            let (key, value) = item?;
+            let name = String::from_utf8(key)?;
+            let value = resolve_primitive(deps.storage, &name, value)?;
+            let tags = load_tags(deps.storage, &name)?;
+            Ok(GetValueResponse {
+                name,
+                value,
+                tags,
+                info: None,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ListValuesResponse { values })
+}
+
+/// Turns a path into the prefix shared by every key below it.
+fn dir_prefix(path: &str) -> String {
+    let path = path.trim_end_matches(PATH_SEPARATOR);
+    if path.is_empty() {
+        return String::new();
+    }
+    format!("{}{}", path, PATH_SEPARATOR)
+}
+
+/// Returns the bound right after a child returned by ListChildren. For a sub-directory that is
+/// past every key inside of it.
+fn child_bound(prefix: &str, child: &str) -> Bound {
+    match child.strip_suffix(PATH_SEPARATOR) {
+        Some(directory) => Bound::inclusive(
+            prefix_end(format!("{}{}{}", prefix, directory, PATH_SEPARATOR).as_bytes())
+                .unwrap_or_default(),
+        ),
+        None => Bound::exclusive(format!("{}{}", prefix, child)),
+    }
+}
+
+/// Returns the cursor to continue after a page read with limit, made from its last item. Only a
+/// full page may be followed by more items, a shorter one reached the end and has no cursor.
+fn next_cursor<T, C>(
+    page: &[T],
+    limit: usize,
+    cursor: impl FnOnce(&T) -> StdResult<C>,
+) -> StdResult<Option<C>> {
+    match page.last() {
+        Some(last) if page.len() == limit => cursor(last).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Takes at most limit items and tells whether that were all of them, by reading one more.
+fn take_page<T>(items: impl Iterator<Item = T>, limit: usize) -> (Vec<T>, bool) {
+    let mut page: Vec<T> = items.take(limit + 1).collect();
+    let done = page.len() <= limit;
+    page.truncate(limit);
+    (page, done)
+}
+
+/// Returns the smallest key that is greater than every key starting with prefix, or None if
+/// there is no such key.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+fn query_bounds(deps: Deps, name: Option<String>) -> StdResult<BoundsResponse> {
+    let name = &get_name_or_default(deps.storage, &name)?;
+    let bounds = BOUNDS.may_load(deps.storage, name)?;
+    Ok(BoundsResponse {
+        name: name.to_string(),
+        min: bounds.as_ref().and_then(|b| b.min),
+        max: bounds.and_then(|b| b.max),
+    })
+}
+
+fn query_top_n(
+    deps: Deps,
+    prefix: String,
+    limit: Option<u32>,
+    order: Option<OrderBy>,
+) -> StdResult<TopNResponse> {
+    let ranked = dir_prefix(&prefix);
+    check_rank_index(deps.storage, &ranked)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let index = RANK_INDEX.prefix(&ranked);
+    let to_ranked = |key: Vec<u8>| {
+        let (value, name) = parse_rank_key(&key)?;
+        Ok(RankedValue { name, value })
+    };
+    if order.unwrap_or(OrderBy::Descending) == OrderBy::Ascending {
+        let values = index
+            .keys(deps.storage, None, None, Order::Ascending)
+            .take(limit)
+            .map(to_ranked)
+            .collect::<StdResult<_>>()?;
+        return Ok(TopNResponse { prefix, values });
+    }
+
+    // Entries sort by value and then by key, so walking them backwards would list keys holding
+    // the same value in reverse. Each value found is read forwards instead.
+    let mut values: Vec<RankedValue> = vec![];
+    let mut end = None;
+    while values.len() < limit {
+        let last = match index
+            .keys(deps.storage, None, end, Order::Descending)
+            .next()
+        {
+            Some(last) => last,
+            None => break,
+        };
+        let (value, _) = parse_rank_key(&last)?;
+        let start = value.u128().to_be_bytes().to_vec();
+        let ties = index
+            .keys(
+                deps.storage,
+                Some(Bound::inclusive(start.clone())),
+                Some(Bound::inclusive(last)),
+                Order::Ascending,
+            )
+            .take(limit - values.len())
+            .map(to_ranked)
+            .collect::<StdResult<Vec<_>>>()?;
+        values.extend(ties);
+        end = Some(Bound::exclusive(start));
+    }
+    Ok(TopNResponse { prefix, values })
+}
+
+/// Fails unless the rank index of the directory prefix is enabled and holds every key below it.
+fn check_rank_index(storage: &dyn Storage, prefix: &str) -> StdResult<()> {
+    if !RANKED_PREFIXES.has(storage, prefix) {
+        return Err(StdError::generic_err(format!(
+            "Rank index is not enabled for {}",
+            prefix
+        )));
+    }
+    if RANK_BACKFILLS.has(storage, prefix) {
+        return Err(StdError::generic_err(format!(
+            "Rank index of {} is incomplete until RebuildIndexes has scanned its keys",
+            prefix
+        )));
+    }
+    Ok(())
+}
+
+/// Counts the entries above the value, so the cost grows with the rank up to MAX_RANK_SCAN.
+fn query_rank_of(deps: Deps, name: Option<String>) -> StdResult<RankOfResponse> {
+    let name = &get_name_or_default(deps.storage, &name)?;
+    let prefix = ranked_prefixes(deps.storage, name)
+        .pop()
+        .ok_or_else(|| StdError::generic_err(format!("{} is not below a ranked path", name)))?;
+    check_rank_index(deps.storage, &prefix)?;
+    let value = load_primitive(deps.storage, name)?
+        .ok_or_else(|| StdError::not_found(type_name::<Primitive>()))?
+        .try_get_uint128()?;
+    let above = match value.u128().checked_add(1) {
+        Some(next) => RANK_INDEX
+            .prefix(&prefix)
+            .keys(
+                deps.storage,
+                Some(Bound::inclusive(next.to_be_bytes().to_vec())),
+                None,
+                Order::Ascending,
+            )
+            .take(MAX_RANK_SCAN + 1)
+            .count(),
+        None => 0,
+    };
+    if above > MAX_RANK_SCAN {
+        return Err(StdError::generic_err(format!(
+            "More than {} values below {} are higher than that of {}",
+            MAX_RANK_SCAN, prefix, name
+        )));
+    }
+    Ok(RankOfResponse {
+        name: name.to_string(),
+        prefix,
+        value,
+        rank: above as u32 + 1,
+    })
+}
+
+fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    Ok(ConfigResponse {
+        owner: config.owner,
+        reverse_index: config.reverse_index,
+        key_rules: config.key_rules,
+        default_key: config.default_key,
+        limits: config.limits,
+        import_finished: config.import_finished,
+        change_retention: config.change_retention,
+    })
+}
+
+fn check_config(config: &Config) -> Result<(), ContractError> {
+    // The default key is written like any other name, so it has to follow the same rules.
+    check_not_reserved(&config.default_key)?;
+    if let Some(reason) = config.key_rules.invalid_reason(&config.default_key) {
+        return Err(ContractError::InvalidKey { reason });
+    }
+    if !config.limits.is_valid() {
+        return Err(ContractError::InvalidLimits {});
+    }
+    Ok(())
+}
+
+/// Returns the key an execute message writes to, after checking that callers may write to it.
+fn writable_name(storage: &dyn Storage, name: &Option<String>) -> Result<String, ContractError> {
+    let name = get_name_or_default(storage, name)?;
+    check_writable(storage, &name)?;
+    Ok(name)
+}
+
+/// Checks that name is not reserved and follows the key rules.
+fn check_writable(storage: &dyn Storage, name: &str) -> Result<(), ContractError> {
+    check_not_reserved(name)?;
+    if let Some(reason) = CONFIG.load(storage)?.key_rules.invalid_reason(name) {
+        return Err(ContractError::InvalidKey { reason });
+    }
+    Ok(())
+}
+
+/// Keys below SYSTEM_PREFIX can only be written by the contract itself. Deletes only check this
+/// and not the key rules, so that keys stored before the rules changed can still be removed.
+fn check_not_reserved(name: &str) -> Result<(), ContractError> {
+    if name.starts_with(SYSTEM_PREFIX) {
+        return Err(ContractError::InvalidKey {
+            reason: format!("names starting with {} are reserved", SYSTEM_PREFIX),
+        });
+    }
+    Ok(())
+}
+
+fn get_name_or_default(storage: &dyn Storage, name: &Option<String>) -> StdResult<String> {
+    match name {
+        None => Ok(CONFIG.load(storage)?.default_key),
+        Some(s) => Ok(s.clone()),
+    }
+}
+
+fn check_is_owner(deps: &DepsMut, address: &Addr) -> Result<bool, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    if &config.owner != address {
+        return Err(ContractError::Unauthorized {});
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::InitialValue;
+    use crate::state::{Limits, MAX_BATCH_OPS, MAX_PREFIX_DELETIONS};
+    use cosmwasm_std::testing::{
+        mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage,
+    };
+    use cosmwasm_std::OwnedDeps;
+    use cosmwasm_std::{from_binary, Attribute, Pair};
+    use cw_storage_plus::Map;
+    use std::cell::Cell;
+
+    /// The layout of stored values before they were indexed, used to set up data written by
+    /// earlier versions of the contract.
+    const LEGACY_DATA: Map<&str, Primitive> = Map::new("data");
+
+    fn query_value_helper(deps: Deps, name: Option<String>) -> GetValueResponse {
+        from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::GetValue {
+                    name,
+                    with_info: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn proper_initialization() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+    }
+
+    #[test]
+    fn set_and_update_value_with_name() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: Some("test1".to_string()),
+            value: Primitive::String("value1".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "set_value")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", "test1")
+                .add_attribute("value", "String(\"value1\")"),
+            res
+        );
+
+        let query_res: GetValueResponse =
+            query_value_helper(deps.as_ref(), Some("test1".to_string()));
+
+        assert_eq!(
+            GetValueResponse {
+                name: "test1".to_string(),
+                value: Primitive::String("value1".to_string()),
+                tags: vec![],
+                info: None,
+            },
+            query_res
+        );
+
+        // Update the value to something else
+        let msg = ExecuteMsg::SetValue {
+            name: Some("test1".to_string()),
+            value: Primitive::String("value2".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let query_res: GetValueResponse =
+            query_value_helper(deps.as_ref(), Some("test1".to_string()));
+
+        assert_eq!(
+            GetValueResponse {
+                name: "test1".to_string(),
+                value: Primitive::String("value2".to_string()),
+                tags: vec![],
+                info: None,
+            },
+            query_res
+        );
+    }
+
+    #[test]
+    fn set_and_update_value_without_name() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::String("value1".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "set_value")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", DEFAULT_KEY)
+                .add_attribute("value", "String(\"value1\")"),
+            res
+        );
+
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+
+        assert_eq!(
+            GetValueResponse {
+                name: DEFAULT_KEY.to_string(),
+                value: Primitive::String("value1".to_string()),
+                tags: vec![],
+                info: None,
+            },
+            query_res
+        );
+
+        // Update the value to something else
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::String("value2".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+
+        assert_eq!(
+            GetValueResponse {
+                name: DEFAULT_KEY.to_string(),
+                value: Primitive::String("value2".to_string()),
+                tags: vec![],
+                info: None,
+            },
+            query_res
+        );
+    }
+
+    #[test]
+    fn cannot_set_nested_vector_primitive() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Vec(vec![Primitive::Vec(vec![])]),
+        };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(ContractError::InvalidPrimitive {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn delete_value_with_name() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: Some("test1".to_string()),
+            value: Primitive::String("value1".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let query_res: GetValueResponse =
+            query_value_helper(deps.as_ref(), Some("test1".to_string()));
+
+        assert_eq!(
+            GetValueResponse {
+                name: "test1".to_string(),
+                value: Primitive::String("value1".to_string()),
+                tags: vec![],
+                info: None,
+            },
+            query_res
+        );
+
+        let msg = ExecuteMsg::DeleteValue {
+            name: Some("test1".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("method", "delete_value")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", "test1")
+        );
+        let query_res = &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetValue {
+                name: Some("test1".to_string()),
+                with_info: None,
+            },
+        );
+        assert!(query_res.is_err());
+    }
+
+    #[test]
+    fn delete_value_without_name() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::String("value1".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+
+        assert_eq!(
+            GetValueResponse {
+                name: DEFAULT_KEY.to_string(),
+                value: Primitive::String("value1".to_string()),
+                tags: vec![],
+                info: None,
+            },
+            query_res
+        );
+
+        let msg = ExecuteMsg::DeleteValue { name: None };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res,
+            Response::new()
+                .add_attribute("method", "delete_value")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", DEFAULT_KEY)
+        );
+        let query_res = &query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetValue {
+                name: None,
+                with_info: None,
+            },
+        );
+        assert!(query_res.is_err());
+    }
+
+    #[test]
+    fn non_creator_cannot_set_value() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let user1 = mock_info("user1", &[]);
+        let msg = ExecuteMsg::SetValue {
+            name: Some("test1".to_string()),
+            value: Primitive::String("value1".to_string()),
+        };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), user1.clone(), msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn non_creator_cannot_delete_value() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::String("value1".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let user1 = mock_info("user1", &[]);
+        let msg = ExecuteMsg::DeleteValue { name: None };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), user1.clone(), msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn batch_applies_ops_in_order() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: Some("test2".to_string()),
+            value: Primitive::Bool(true),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Batch {
+            ops: vec![
+                Op::Set {
+                    name: Some("test1".to_string()),
+                    value: Primitive::String("value1".to_string()),
+                },
+                Op::Delete {
+                    name: Some("test2".to_string()),
+                },
+                Op::SetIf {
+                    name: Some("test1".to_string()),
+                    expected: Some(Primitive::String("value1".to_string())),
+                    value: Primitive::String("value2".to_string()),
+                },
+                Op::SetIf {
+                    name: None,
+                    expected: None,
+                    value: Primitive::Bool(false),
+                },
+            ],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "batch")
+                .add_attribute("sender", "creator")
+                .add_attribute("ops", "4")
+                .add_events(vec![
+                    Event::new("batch_op")
+                        .add_attribute("index", "0")
+                        .add_attribute("op", "set")
+                        .add_attribute("name", "test1")
+                        .add_attribute("value", "String(\"value1\")"),
+                    Event::new("batch_op")
+                        .add_attribute("index", "1")
+                        .add_attribute("op", "delete")
+                        .add_attribute("name", "test2"),
+                    Event::new("batch_op")
+                        .add_attribute("index", "2")
+                        .add_attribute("op", "set_if")
+                        .add_attribute("name", "test1")
+                        .add_attribute("value", "String(\"value2\")"),
+                    Event::new("batch_op")
+                        .add_attribute("index", "3")
+                        .add_attribute("op", "set_if")
+                        .add_attribute("name", DEFAULT_KEY)
+                        .add_attribute("value", "Bool(false)"),
+                ]),
+            res
+        );
+
+        let query_res: GetValueResponse =
+            query_value_helper(deps.as_ref(), Some("test1".to_string()));
+        assert_eq!(Primitive::String("value2".to_string()), query_res.value);
+
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+        assert_eq!(Primitive::Bool(false), query_res.value);
+
+        let query_res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetValue {
+                name: Some("test2".to_string()),
+                with_info: None,
+            },
+        );
+        assert!(query_res.is_err());
+    }
+
+    #[test]
+    fn batch_fails_when_condition_not_met() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Batch {
+            ops: vec![Op::SetIf {
+                name: Some("test1".to_string()),
+                expected: Some(Primitive::Bool(true)),
+                value: Primitive::Bool(false),
+            }],
+        };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::ConditionFailed {
+                name: "test1".to_string()
+            },
+            res.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn batch_rejects_invalid_sizes() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Batch { ops: vec![] };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(ContractError::EmptyBatch {}, res.unwrap_err());
+
+        let msg = ExecuteMsg::Batch {
+            ops: vec![Op::Delete { name: None }; MAX_BATCH_OPS + 1],
+        };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::BatchTooLarge { max: MAX_BATCH_OPS },
+            res.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn non_creator_cannot_batch() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let user1 = mock_info("user1", &[]);
+        let msg = ExecuteMsg::Batch {
+            ops: vec![Op::Delete { name: None }],
+        };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), user1.clone(), msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+
+        // Ownership is checked before the size of the batch.
+        let msg = ExecuteMsg::Batch { ops: vec![] };
+        let res = execute(deps.as_mut(), mock_env(), user1.clone(), msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let msg = ExecuteMsg::Batch {
+            ops: vec![Op::Delete { name: None }; MAX_BATCH_OPS + 1],
+        };
+        let res = execute(deps.as_mut(), mock_env(), user1, msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn arithmetic_updates_value_in_place() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // A missing key starts at zero.
+        let msg = ExecuteMsg::Increment {
+            name: Some("counter".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "increment")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", "counter")
+                .add_attribute("value", "1")
+                .set_data(
+                    to_binary(&ArithmeticResponse {
+                        name: "counter".to_string(),
+                        value: Uint128::new(1)
+                    })
+                    .unwrap()
+                ),
+            res
+        );
+
+        let msg = ExecuteMsg::Add {
+            name: Some("counter".to_string()),
+            amount: Uint128::new(10),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let data: ArithmeticResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(Uint128::new(11), data.value);
+
+        let msg = ExecuteMsg::Sub {
+            name: Some("counter".to_string()),
+            amount: Uint128::new(5),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Decrement {
+            name: Some("counter".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let query_res: GetValueResponse =
+            query_value_helper(deps.as_ref(), Some("counter".to_string()));
+        assert_eq!(Primitive::Uint128(Uint128::new(5)), query_res.value);
+    }
+
+    #[test]
+    fn arithmetic_rejects_overflow_and_wrong_type() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Decrement { name: None };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Std(StdError::Overflow { .. })
+        ));
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Uint128(Uint128::MAX),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Increment { name: None };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Std(StdError::Overflow { .. })
+        ));
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Bool(true),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Increment { name: None };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Std(StdError::ParseErr { .. })
+        ));
+    }
+
+    #[test]
+    fn arithmetic_respects_bounds() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetBounds {
+            name: None,
+            min: Some(Uint128::new(10)),
+            max: Some(Uint128::new(5)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(ContractError::InvalidBounds {}, res.unwrap_err());
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Uint128(Uint128::new(1)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetBounds {
+            name: None,
+            min: Some(Uint128::new(1)),
+            max: Some(Uint128::new(2)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let bounds: BoundsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetBounds { name: None },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            BoundsResponse {
+                name: DEFAULT_KEY.to_string(),
+                min: Some(Uint128::new(1)),
+                max: Some(Uint128::new(2)),
+            },
+            bounds
+        );
+
+        let msg = ExecuteMsg::Increment { name: None };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::Increment { name: None };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::OutOfBounds {
+                name: DEFAULT_KEY.to_string(),
+                value: Uint128::new(3)
+            },
+            res.unwrap_err()
+        );
+
+        let msg = ExecuteMsg::Sub {
+            name: None,
+            amount: Uint128::new(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::OutOfBounds {
+                name: DEFAULT_KEY.to_string(),
+                value: Uint128::zero()
+            },
+            res.unwrap_err()
+        );
+
+        // Plain sets are held to the same bounds.
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Uint128(Uint128::new(100)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::OutOfBounds { .. }
+        ));
+
+        // Deleting the key removes its bounds.
+        let msg = ExecuteMsg::DeleteValue { name: None };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let bounds: BoundsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetBounds { name: None },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(None, bounds.min);
+        assert_eq!(None, bounds.max);
+    }
+
+    #[test]
+    fn non_creator_cannot_update_arithmetic() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let user1 = mock_info("user1", &[]);
+        let msg = ExecuteMsg::Increment { name: None };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), user1.clone(), msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+
+        let msg = ExecuteMsg::SetBounds {
+            name: None,
+            min: None,
+            max: Some(Uint128::new(1)),
+        };
+        let res: Result<Response, ContractError> =
+            execute(deps.as_mut(), mock_env(), user1.clone(), msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn vector_operations_update_value_in_place() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // A missing key starts as an empty vector.
+        let msg = ExecuteMsg::VecPush {
+            name: None,
+            value: Primitive::Uint128(Uint128::new(1)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "vec_push")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", DEFAULT_KEY)
+                .add_attribute("value", "Uint128(Uint128(1))"),
+            res
+        );
+
+        let msg = ExecuteMsg::VecPush {
+            name: None,
+            value: Primitive::Uint128(Uint128::new(3)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::VecInsert {
+            name: None,
+            index: 1,
+            value: Primitive::Uint128(Uint128::new(2)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::VecSet {
+            name: None,
+            index: 0,
+            value: Primitive::Bool(true),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "vec_set")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", DEFAULT_KEY)
+                .add_attribute("index", "0")
+                .add_attribute("value", "Bool(true)"),
+            res
+        );
+
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+        assert_eq!(
+            Primitive::Vec(vec![
+                Primitive::Bool(true),
+                Primitive::Uint128(Uint128::new(2)),
+                Primitive::Uint128(Uint128::new(3)),
+            ]),
+            query_res.value
+        );
+
+        let msg = ExecuteMsg::VecRemove {
+            name: None,
+            index: 1,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "vec_remove")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", DEFAULT_KEY)
+                .add_attribute("index", "1")
+                .add_attribute("value", "Uint128(Uint128(2))"),
+            res
+        );
+
+        let msg = ExecuteMsg::VecPop { name: None };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+        assert_eq!(Primitive::Vec(vec![Primitive::Bool(true)]), query_res.value);
+    }
+
+    #[test]
+    fn vector_operations_reject_invalid_input() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::VecPop { name: None };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(ContractError::EmptyVector {}, res.unwrap_err());
+
+        let msg = ExecuteMsg::VecPush {
+            name: None,
+            value: Primitive::Vec(vec![]),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(ContractError::InvalidPrimitive {}, res.unwrap_err());
+
+        let msg = ExecuteMsg::VecPush {
+            name: None,
+            value: Primitive::Bool(true),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::VecInsert {
+            name: None,
+            index: 2,
+            value: Primitive::Bool(false),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::IndexOutOfRange { index: 2, len: 1 },
+            res.unwrap_err()
+        );
+
+        let msg = ExecuteMsg::VecRemove {
+            name: None,
+            index: 1,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::IndexOutOfRange { index: 1, len: 1 },
+            res.unwrap_err()
+        );
+
+        let msg = ExecuteMsg::VecSet {
+            name: None,
+            index: 1,
+            value: Primitive::Bool(false),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::IndexOutOfRange { index: 1, len: 1 },
+            res.unwrap_err()
+        );
+
+        let msg = ExecuteMsg::SetValue {
+            name: Some("test1".to_string()),
+            value: Primitive::Bool(true),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::VecPop {
+            name: Some("test1".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert!(matches!(
+            res.unwrap_err(),
+            ContractError::Std(StdError::ParseErr { .. })
+        ));
+
+        let user1 = mock_info("user1", &[]);
+        let msg = ExecuteMsg::VecPop { name: None };
+        let res = execute(deps.as_mut(), mock_env(), user1, msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn set_operations_keep_elements_unique() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let contains = |deps: Deps, value: &str| -> bool {
+            let res: VecContainsResponse = from_binary(
+                &query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::VecContains {
+                        name: None,
+                        value: Primitive::String(value.to_string()),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res.contains
+        };
+        assert!(!contains(deps.as_ref(), "addr1"));
+
+        let msg = ExecuteMsg::SetAdd {
+            name: None,
+            value: Primitive::String("addr1".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "set_add")
+                .add_attribute("sender", "creator")
+                .add_attribute("name", DEFAULT_KEY)
+                .add_attribute("value", "String(\"addr1\")")
+                .add_attribute("changed", "true"),
+            res
+        );
+        assert!(contains(deps.as_ref(), "addr1"));
+
+        // Adding the same element again is a no-op.
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Some(&Attribute::new("changed", "false")),
+            res.attributes.last()
+        );
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+        assert_eq!(
+            Primitive::Vec(vec![Primitive::String("addr1".to_string())]),
+            query_res.value
+        );
+
+        let msg = ExecuteMsg::SetRemove {
+            name: None,
+            value: Primitive::String("addr1".to_string()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            Some(&Attribute::new("changed", "true")),
+            res.attributes.last()
+        );
+        assert!(!contains(deps.as_ref(), "addr1"));
+
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Some(&Attribute::new("changed", "false")),
+            res.attributes.last()
+        );
+    }
+
+    #[test]
+    fn unique_keys_reject_duplicates() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let duplicates = Primitive::Vec(vec![Primitive::Bool(true), Primitive::Bool(true)]);
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: duplicates.clone(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetUnique {
+            name: None,
+            unique: true,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone());
+        assert_eq!(
+            ContractError::DuplicateElement {
+                name: DEFAULT_KEY.to_string()
+            },
+            res.unwrap_err()
+        );
+
+        let msg_set = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Vec(vec![Primitive::Bool(true)]),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg_set).unwrap();
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: duplicates.clone(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::DuplicateElement {
+                name: DEFAULT_KEY.to_string()
+            },
+            res.unwrap_err()
+        );
+
+        let msg = ExecuteMsg::VecPush {
+            name: None,
+            value: Primitive::Bool(true),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::DuplicateElement {
+                name: DEFAULT_KEY.to_string()
+            },
+            res.unwrap_err()
+        );
+
+        let msg = ExecuteMsg::SetUnique {
+            name: None,
+            unique: false,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: duplicates,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+    }
+
+    #[test]
+    fn query_vector_in_pages() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let values: Vec<Primitive> = (0..150_u128)
+            .map(|i| Primitive::Uint128(Uint128::new(i)))
+            .collect();
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Vec(values.clone()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let slice = |deps: Deps, offset: Option<u32>, limit: Option<u32>| -> VecSliceResponse {
+            from_binary(
+                &query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::GetVecSlice {
+                        name: None,
+                        offset,
+                        limit,
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap()
+        };
+
+        let res = slice(deps.as_ref(), None, None);
+        assert_eq!(
+            VecSliceResponse {
+                name: DEFAULT_KEY.to_string(),
+                offset: 0,
+                values: values[..DEFAULT_LIMIT as usize].to_vec(),
+                length: 150,
+            },
+            res
+        );
+
+        let res = slice(deps.as_ref(), Some(20), Some(5));
+        assert_eq!(values[20..25].to_vec(), res.values);
+
+        // The limit is capped and the last page may be short.
+        let res = slice(deps.as_ref(), Some(0), Some(1000));
+        assert_eq!(MAX_LIMIT as usize, res.values.len());
+        let res = slice(deps.as_ref(), Some(140), Some(20));
+        assert_eq!(values[140..].to_vec(), res.values);
+        let res = slice(deps.as_ref(), Some(200), None);
+        assert!(res.values.is_empty());
+
+        let res: VecLengthResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetVecLength { name: None },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            VecLengthResponse {
+                name: DEFAULT_KEY.to_string(),
+                length: 150,
+            },
+            res
+        );
+
+        let res: VecLengthResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetVecLength {
+                    name: Some("missing".to_string()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(0, res.length);
+    }
+
+    #[test]
+    fn large_vectors_are_transparent_to_reads() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let mut values: Vec<Primitive> = (0..VEC_CHUNK_THRESHOLD as u128 + 10)
+            .map(|i| Primitive::Uint128(Uint128::new(i)))
+            .collect();
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Vec(values.clone()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert!(VEC_LENGTHS.has(&deps.storage, DEFAULT_KEY));
+
+        let msg = ExecuteMsg::VecPush {
+            name: None,
+            value: Primitive::Bool(true),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        values.push(Primitive::Bool(true));
+
+        let msg = ExecuteMsg::VecRemove {
+            name: None,
+            index: 0,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        values.remove(0);
+
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+        assert_eq!(Primitive::Vec(values.clone()), query_res.value);
+
+        let res: VecSliceResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetVecSlice {
+                    name: None,
+                    offset: Some(30),
+                    limit: Some(5),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(values[30..35].to_vec(), res.values);
+        assert_eq!(values.len() as u32, res.length);
+
+        // Overwriting with a scalar drops the chunks.
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Bool(false),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert!(!VEC_LENGTHS.has(&deps.storage, DEFAULT_KEY));
+        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+        assert_eq!(Primitive::Bool(false), query_res.value);
+    }
+
+    /// Storage counting the entries read and written, to measure what a message touches.
+    #[derive(Default)]
+    struct CountingStorage {
+        inner: MockStorage,
+        accesses: Cell<usize>,
+    }
+
+    impl CountingStorage {
+        fn touch(&self) {
+            self.accesses.set(self.accesses.get() + 1);
+        }
+    }
+
+    impl Storage for CountingStorage {
+        fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+            self.touch();
+            self.inner.get(key)
+        }
+
+        fn range<'a>(
+            &'a self,
+            start: Option<&[u8]>,
+            end: Option<&[u8]>,
+            order: Order,
+        ) -> Box<dyn Iterator<Item = Pair> + 'a> {
+            Box::new(
+                self.inner
+                    .range(start, end, order)
+                    .inspect(move |_| self.touch()),
+            )
+        }
+
+        fn set(&mut self, key: &[u8], value: &[u8]) {
+            self.touch();
+            self.inner.set(key, value)
+        }
+
+        fn remove(&mut self, key: &[u8]) {
+            self.touch();
+            self.inner.remove(key)
+        }
+    }
+
+    #[test]
+    fn chunked_vec_element_ops_touch_few_entries() {
+        // The storage entries touched by pushing, setting and popping one element of a chunked
+        // Vec holding len elements.
+        let costs = |len: u128| -> Vec<usize> {
+            let mut deps = OwnedDeps {
+                storage: CountingStorage::default(),
+                api: MockApi::default(),
+                querier: MockQuerier::<Empty>::new(&[]),
+            };
+            let info = mock_info("creator", &[]);
+            instantiate(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                InstantiateMsg::default(),
+            )
+            .unwrap();
+            let msg = ExecuteMsg::SetValue {
+                name: None,
+                value: Primitive::Vec(
+                    (0..len)
+                        .map(|i| Primitive::Uint128(Uint128::new(i)))
+                        .collect(),
+                ),
+            };
+            execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+            let msgs = vec![
+                ExecuteMsg::VecPush {
+                    name: None,
+                    value: Primitive::Bool(true),
+                },
+                ExecuteMsg::VecSet {
+                    name: None,
+                    index: len as u32 / 2,
+                    value: Primitive::Bool(false),
+                },
+                ExecuteMsg::VecPop { name: None },
+            ];
+            msgs.into_iter()
+                .map(|msg| {
+                    deps.storage.accesses.set(0);
+                    execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+                    deps.storage.accesses.get()
+                })
+                .collect()
+        };
+
+        let small = costs(VEC_CHUNK_THRESHOLD as u128 * 2);
+        let large = costs(VEC_CHUNK_THRESHOLD as u128 * 256);
+        for (small, large) in small.iter().zip(large.iter()) {
+            // A Vec 128 times as long only adds the 7 extra levels of its element tree, each
+            // reading two children and writing their parent.
+            assert!(*large <= small + 7 * 3, "{} vs {}", large, small);
+        }
+    }
+
+    #[test]
+    fn chunk_vectors_migrates_existing_blobs() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // Blobs written by an earlier version of the contract.
+        let values: Vec<Primitive> = (0..VEC_CHUNK_THRESHOLD as u128 + 1)
+            .map(|i| Primitive::Uint128(Uint128::new(i)))
+            .collect();
+        for name in ["a", "b", "c"].iter() {
+            LEGACY_DATA
+                .save(&mut deps.storage, name, &Primitive::Vec(values.clone()))
+                .unwrap();
+        }
+        LEGACY_DATA
+            .save(&mut deps.storage, "d", &Primitive::Bool(true))
+            .unwrap();
+
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "migrate")
+                .add_attribute("version", CONTRACT_VERSION),
+            res
+        );
+
+        let msg = ExecuteMsg::ChunkVectors {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "chunk_vectors")
+                .add_attribute("sender", "creator")
+                .add_attribute("converted", "2")
+                .add_attribute("next_start_after", "b"),
+            res
+        );
+        assert!(VEC_LENGTHS.has(&deps.storage, "b"));
+        assert!(!VEC_LENGTHS.has(&deps.storage, "c"));
+
+        let msg = ExecuteMsg::ChunkVectors {
+            start_after: Some("b".to_string()),
+            limit: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "chunk_vectors")
+                .add_attribute("sender", "creator")
+                .add_attribute("converted", "1"),
+            res
+        );
+
+        for name in ["a", "b", "c"].iter() {
+            assert!(VEC_LENGTHS.has(&deps.storage, name));
+            let query_res = query_value_helper(deps.as_ref(), Some(name.to_string()));
+            assert_eq!(Primitive::Vec(values.clone()), query_res.value);
+        }
+
+        let user1 = mock_info("user1", &[]);
+        let msg = ExecuteMsg::ChunkVectors {
+            start_after: None,
+            limit: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), user1, msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn list_children_of_path() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        for name in [
+            "oracle",
+            "oracle-x",
+            "oracle/btc/price",
+            "oracle/btc/volume",
+            "oracle/btc0",
+            "oracle/eth/price",
+            "oracle/fee",
+        ]
+        .iter()
+        {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name.to_string()),
+                value: Primitive::Bool(true),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let list =
+            |deps: Deps, path: Option<&str>, start_after: Option<&str>, limit: Option<u32>| {
+                let res: ListChildrenResponse = from_binary(
+                    &query(
+                        deps,
+                        mock_env(),
+                        QueryMsg::ListChildren {
+                            path: path.map(String::from),
+                            start_after: start_after.map(String::from),
+                            limit,
+                        },
+                    )
+                    .unwrap(),
+                )
+                .unwrap();
+                res.children
+            };
+
+        assert_eq!(
+            vec!["oracle", "oracle-x", "oracle/"],
+            list(deps.as_ref(), None, None, None)
+        );
+        assert_eq!(
+            vec!["btc/", "btc0", "eth/", "fee"],
+            list(deps.as_ref(), Some("oracle"), None, None)
+        );
+        assert_eq!(
+            vec!["price", "volume"],
+            list(deps.as_ref(), Some("oracle/btc/"), None, None)
+        );
+        assert!(list(deps.as_ref(), Some("missing"), None, None).is_empty());
+
+        // Page through the children one at a time.
+        assert_eq!(
+            vec!["btc/"],
+            list(deps.as_ref(), Some("oracle"), None, Some(1))
+        );
+        assert_eq!(
+            vec!["btc0"],
+            list(deps.as_ref(), Some("oracle"), Some("btc/"), Some(1))
+        );
+        assert_eq!(
+            vec!["eth/", "fee"],
+            list(deps.as_ref(), Some("oracle"), Some("btc0"), None)
+        );
+    }
+
+    #[test]
+    fn delete_prefix_in_bounded_batches() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let mut names: Vec<String> = (0..MAX_PREFIX_DELETIONS + 5)
+            .map(|i| format!("tree/{}", i))
+            .collect();
+        names.push("tree".to_string());
+        names.push("tree-x".to_string());
+        for name in names {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name),
+                value: Primitive::Bool(true),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let msg = ExecuteMsg::DeletePrefix {
+            path: "tree".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "delete_prefix")
+                .add_attribute("sender", "creator")
+                .add_attribute("path", "tree")
+                .add_attribute("deleted", MAX_PREFIX_DELETIONS.to_string())
+                .add_attribute("done", "false"),
+            res
+        );
+
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "delete_prefix")
+                .add_attribute("sender", "creator")
+                .add_attribute("path", "tree")
+                .add_attribute("deleted", "5")
+                .add_attribute("done", "true"),
+            res
+        );
+
+        let res: ListChildrenResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ListChildren {
+                    path: None,
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(vec!["tree", "tree-x"], res.children);
+
+        let msg = ExecuteMsg::DeletePrefix {
+            path: "/".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::InvalidPath {
+                path: "/".to_string()
+            },
+            res.unwrap_err()
+        );
+
+        let user1 = mock_info("user1", &[]);
+        let msg = ExecuteMsg::DeletePrefix {
+            path: "tree".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), user1, msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn list_values_by_prefix_and_range() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let large: Vec<Primitive> = (0..VEC_CHUNK_THRESHOLD as u128 + 1)
+            .map(|i| Primitive::Uint128(Uint128::new(i)))
+            .collect();
+        let entries = [
+            ("a", Primitive::Bool(true)),
+            ("fee_a", Primitive::Uint128(Uint128::new(1))),
+            ("fee_b", Primitive::Vec(large)),
+            ("fee_c", Primitive::Uint128(Uint128::new(3))),
+            ("m", Primitive::Bool(false)),
+            ("z", Primitive::String("z".to_string())),
+        ];
+        for (name, value) in entries.iter() {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name.to_string()),
+                value: value.clone(),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        let expected = |range: std::ops::Range<usize>| -> Vec<GetValueResponse> {
+            entries[range]
+                .iter()
+                .map(|(name, value)| GetValueResponse {
+                    name: name.to_string(),
+                    value: value.clone(),
+                    tags: vec![],
+                    info: None,
+                })
+                .collect()
+        };
+        let list = |deps: Deps, msg: QueryMsg| -> Vec<GetValueResponse> {
+            let res: ListValuesResponse =
+                from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.values
+        };
+
+        // Chunked vectors are returned in full.
+        let res = list(
+            deps.as_ref(),
+            QueryMsg::ListByPrefix {
+                prefix: "fee_".to_string(),
+                start_after: None,
+                limit: None,
+            },
+        );
+        assert_eq!(expected(1..4), res);
+
+        let res = list(
+            deps.as_ref(),
+            QueryMsg::ListByPrefix {
+                prefix: "fee_".to_string(),
+                start_after: Some("fee_a".to_string()),
+                limit: Some(1),
+            },
+        );
+        assert_eq!(expected(2..3), res);
+
+        let res = list(
+            deps.as_ref(),
+            QueryMsg::ListRange {
+                start: Some("a".to_string()),
+                end: Some("m".to_string()),
+                order: None,
+                limit: None,
+            },
+        );
+        assert_eq!(expected(0..4), res);
+
+        let res = list(
+            deps.as_ref(),
+            QueryMsg::ListRange {
+                start: Some("fee_b".to_string()),
+                end: None,
+                order: Some(OrderBy::Descending),
+                limit: Some(2),
+            },
+        );
+        let mut descending = expected(4..6);
+        descending.reverse();
+        assert_eq!(descending, res);
+    }
+
+    #[test]
+    fn list_values_enforces_page_size() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        for i in 0..MAX_LIMIT + 1 {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(format!("key{:03}", i)),
+                value: Primitive::Bool(true),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let res: ListValuesResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ListRange {
+                    start: None,
+                    end: None,
+                    order: None,
+                    limit: Some(MAX_LIMIT * 2),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(MAX_LIMIT as usize, res.values.len());
+
+        let res: ListValuesResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ListByPrefix {
+                    prefix: "key".to_string(),
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(DEFAULT_LIMIT as usize, res.values.len());
+    }
+
+    fn list_by_type_helper(deps: Deps, primitive_type: PrimitiveType) -> Vec<String> {
+        let res: ListValuesResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::ListByType {
+                    r#type: primitive_type,
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.values.into_iter().map(|v| v.name).collect()
+    }
+
+    #[test]
+    fn list_values_by_type() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let large: Vec<Primitive> = (0..VEC_CHUNK_THRESHOLD as u128 + 1)
+            .map(|i| Primitive::Uint128(Uint128::new(i)))
+            .collect();
+        let entries = [
+            ("flag_a", Primitive::Bool(true)),
+            ("flag_b", Primitive::Bool(false)),
+            ("flag_c", Primitive::Bool(true)),
+            ("list", Primitive::Vec(large)),
+            ("price", Primitive::Uint128(Uint128::new(1))),
+        ];
+        for (name, value) in entries.iter() {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name.to_string()),
+                value: value.clone(),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        assert_eq!(
+            vec!["flag_a", "flag_b", "flag_c"],
+            list_by_type_helper(deps.as_ref(), PrimitiveType::Bool)
+        );
+        assert_eq!(
+            vec!["list"],
+            list_by_type_helper(deps.as_ref(), PrimitiveType::Vec)
+        );
+        assert!(list_by_type_helper(deps.as_ref(), PrimitiveType::String).is_empty());
+
+        let res: ListValuesResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ListByType {
+                    r#type: PrimitiveType::Bool,
+                    start_after: Some("flag_a".to_string()),
+                    limit: Some(1),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![GetValueResponse {
+                name: "flag_b".to_string(),
+                value: Primitive::Bool(false),
+                tags: vec![],
+                info: None,
+            }],
+            res.values
+        );
+
+        // Overwriting with another type moves the key to the other index.
+        let msg = ExecuteMsg::SetValue {
+            name: Some("flag_b".to_string()),
+            value: Primitive::String("off".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::DeleteValue {
+            name: Some("flag_c".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Increment {
+            name: Some("counter".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        assert_eq!(
+            vec!["flag_a"],
+            list_by_type_helper(deps.as_ref(), PrimitiveType::Bool)
+        );
+        assert_eq!(
+            vec!["flag_b"],
+            list_by_type_helper(deps.as_ref(), PrimitiveType::String)
+        );
+        assert_eq!(
+            vec!["counter", "price"],
+            list_by_type_helper(deps.as_ref(), PrimitiveType::Uint128)
+        );
+    }
+
+    #[test]
+    fn rebuild_indexes_for_existing_values() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        for name in ["a", "b", "c"].iter() {
+            LEGACY_DATA
+                .save(&mut deps.storage, name, &Primitive::Bool(true))
+                .unwrap();
+        }
+        assert!(list_by_type_helper(deps.as_ref(), PrimitiveType::Bool).is_empty());
+
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            Response::new()
+                .add_attribute("method", "rebuild_indexes")
+                .add_attribute("sender", "creator")
+                .add_attribute("indexed", "2")
+                .add_attribute("next_start_after", "b"),
+            res
+        );
+
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: Some("b".to_string()),
+            limit: Some(2),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            vec!["a", "b", "c"],
+            list_by_type_helper(deps.as_ref(), PrimitiveType::Bool)
+        );
+
+        // Rebuilding again does not duplicate anything.
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: None,
+            limit: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            vec!["a", "b", "c"],
+            list_by_type_helper(deps.as_ref(), PrimitiveType::Bool)
+        );
+
+        let user1 = mock_info("user1", &[]);
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: None,
+            limit: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), user1, msg);
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+    }
+
+    fn find_keys_helper(deps: Deps, value: Primitive) -> Vec<String> {
+        let res: FindKeysByValueResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::FindKeysByValue {
+                    value,
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.keys
+    }
+
+    #[test]
+    fn find_keys_by_value() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            reverse_index: Some(true),
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let config: ConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert!(config.reverse_index);
+
+        let contract = Primitive::String("contract_x".to_string());
+        for name in ["router", "pool/a", "pool/b"].iter() {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name.to_string()),
+                value: contract.clone(),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::SetValue {
+            name: Some("list".to_string()),
+            value: Primitive::Vec(vec![contract.clone()]),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        assert_eq!(
+            vec!["pool/a", "pool/b", "router"],
+            find_keys_helper(deps.as_ref(), contract.clone())
+        );
+
+        let res: FindKeysByValueResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::FindKeysByValue {
+                    value: contract.clone(),
+                    start_after: Some("pool/a".to_string()),
+                    limit: Some(1),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(vec!["pool/b"], res.keys);
+
+        // Overwrites and deletes are reflected in the index.
+        let msg = ExecuteMsg::SetValue {
+            name: Some("router".to_string()),
+            value: Primitive::String("contract_y".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Batch {
+            ops: vec![Op::Delete {
+                name: Some("pool/a".to_string()),
+            }],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        assert_eq!(
+            vec!["pool/b"],
+            find_keys_helper(deps.as_ref(), contract.clone())
+        );
+        assert_eq!(
+            vec!["router"],
+            find_keys_helper(deps.as_ref(), Primitive::String("contract_y".to_string()))
+        );
+
+        let msg = ExecuteMsg::Add {
+            name: Some("counter".to_string()),
+            amount: Uint128::new(2),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Increment {
+            name: Some("counter".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert!(find_keys_helper(deps.as_ref(), Primitive::Uint128(Uint128::new(2))).is_empty());
+        assert_eq!(
+            vec!["counter"],
+            find_keys_helper(deps.as_ref(), Primitive::Uint128(Uint128::new(3)))
+        );
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::FindKeysByValue {
+                value: Primitive::Vec(vec![]),
+                start_after: None,
+                limit: None,
+            },
+        );
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn find_keys_by_value_requires_reverse_index() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Bool(true),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert!(VALUE_KEYS
+            .prefix(&Primitive::Bool(true).hash().unwrap())
+            .keys(&deps.storage, None, None, Order::Ascending)
+            .next()
+            .is_none());
+
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::FindKeysByValue {
+                value: Primitive::Bool(true),
+                start_after: None,
+                limit: None,
+            },
+        );
+        assert_eq!(
+            StdError::generic_err("Reverse index is not enabled"),
+            res.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn rebuild_indexes_fills_reverse_index() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            reverse_index: Some(true),
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        LEGACY_DATA
+            .save(&mut deps.storage, "a", &Primitive::Bool(true))
+            .unwrap();
+        assert!(find_keys_helper(deps.as_ref(), Primitive::Bool(true)).is_empty());
+
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: None,
+            limit: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            vec!["a"],
+            find_keys_helper(deps.as_ref(), Primitive::Bool(true))
+        );
+    }
+
+    fn top_n_helper(deps: Deps, prefix: &str, order: Option<OrderBy>) -> Vec<(String, u128)> {
+        let res: TopNResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::TopN {
+                    prefix: prefix.to_string(),
+                    limit: None,
+                    order,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.values
+            .into_iter()
+            .map(|v| (v.name, v.value.u128()))
+            .collect()
+    }
+
+    fn rank_of_helper(deps: Deps, name: &str) -> u32 {
+        let res: RankOfResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::RankOf {
+                    name: Some(name.to_string()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.rank
+    }
+
+    #[test]
+    fn top_n_orders_ties_by_key() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg::default();
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        for (name, value) in [("d", 1), ("c", 2), ("b", 2), ("a", 2), ("e", 3)].iter() {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name.to_string()),
+                value: Primitive::Uint128(Uint128::new(*value)),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::SetRanked {
+            path: "".to_string(),
+            ranked: true,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: None,
+            limit: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let top = |limit, order| -> Vec<String> {
+            let res: TopNResponse = from_binary(
+                &query(
+                    deps.as_ref(),
+                    mock_env(),
+                    QueryMsg::TopN {
+                        prefix: "".to_string(),
+                        limit: Some(limit),
+                        order: Some(order),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            res.values.into_iter().map(|value| value.name).collect()
+        };
+        assert_eq!(vec!["e", "a", "b", "c", "d"], top(10, OrderBy::Descending));
+        // The limit can cut through keys holding the same value.
+        assert_eq!(vec!["e", "a", "b"], top(3, OrderBy::Descending));
+        assert_eq!(vec!["d", "a", "b"], top(3, OrderBy::Ascending));
+    }
+
+    #[test]
+    fn rank_index_follows_writes() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetRanked {
+            path: "scores".to_string(),
+            ranked: true,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "set_ranked"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("path", "scores"),
+                Attribute::new("ranked", "true"),
+            ]
+        );
+
+        for (name, value) in [
+            ("scores/a", 5),
+            ("scores/b", 9),
+            ("scores/c", 5),
+            ("other", 7),
+        ]
+        .iter()
+        {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name.to_string()),
+                value: Primitive::Uint128(Uint128::new(*value)),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        let msg = ExecuteMsg::SetValue {
+            name: Some("scores/d".to_string()),
+            value: Primitive::String("n/a".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        assert_eq!(
+            vec![
+                ("scores/b".to_string(), 9),
+                ("scores/a".to_string(), 5),
+                ("scores/c".to_string(), 5),
+            ],
+            top_n_helper(deps.as_ref(), "scores", None)
+        );
+        assert_eq!(
+            vec![
+                ("scores/a".to_string(), 5),
+                ("scores/c".to_string(), 5),
+                ("scores/b".to_string(), 9),
+            ],
+            top_n_helper(deps.as_ref(), "scores/", Some(OrderBy::Ascending))
+        );
+        assert_eq!(1, rank_of_helper(deps.as_ref(), "scores/b"));
+        assert_eq!(2, rank_of_helper(deps.as_ref(), "scores/a"));
+        assert_eq!(2, rank_of_helper(deps.as_ref(), "scores/c"));
+
+        // Arithmetic, overwrites and deletes move the entries.
+        let msg = ExecuteMsg::Add {
+            name: Some("scores/a".to_string()),
+            amount: Uint128::new(10),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetValue {
+            name: Some("scores/b".to_string()),
+            value: Primitive::Bool(false),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::DeleteValue {
+            name: Some("scores/c".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::Increment {
+            name: Some("scores/e".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        assert_eq!(
+            vec![("scores/a".to_string(), 15), ("scores/e".to_string(), 1)],
+            top_n_helper(deps.as_ref(), "scores", None)
+        );
+        assert_eq!(2, rank_of_helper(deps.as_ref(), "scores/e"));
+
+        // RankOf gives up on values with more than MAX_RANK_SCAN entries above them.
+        for i in 0..MAX_RANK_SCAN as u128 {
+            let entry = rank_key(Uint128::new(100 + i), &format!("x{}", i));
+            RANK_INDEX
+                .save(&mut deps.storage, ("scores/", &entry), &Empty {})
+                .unwrap();
+        }
+        assert_eq!(
+            MAX_RANK_SCAN as u32 + 1,
+            rank_of_helper(deps.as_ref(), "scores/a")
+        );
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::RankOf {
+                name: Some("scores/e".to_string()),
+            },
+        );
+        assert!(res.is_err());
+
+        // Keys outside of a ranked path and non Uint128 values cannot be ranked.
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::RankOf {
+                name: Some("other".to_string()),
+            },
+        );
+        assert!(res.is_err());
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::RankOf {
+                name: Some("scores/d".to_string()),
+            },
+        );
+        assert!(res.is_err());
+        let res = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::TopN {
+                prefix: "".to_string(),
+                limit: None,
+                order: None,
+            },
+        );
+        assert_eq!(
+            StdError::generic_err("Rank index is not enabled for "),
+            res.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn rank_index_is_backfilled_and_cleared() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        for i in 0..(MAX_PREFIX_DELETIONS as u128 + 1) {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(format!("scores/{:03}", i)),
+                value: Primitive::Uint128(Uint128::new(i)),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let msg = ExecuteMsg::SetRanked {
+            path: "".to_string(),
+            ranked: true,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        // The queries fail until RebuildIndexes has scanned every key.
+        let incomplete = StdError::generic_err(
+            "Rank index of  is incomplete until RebuildIndexes has scanned its keys",
+        );
+        let top_n = QueryMsg::TopN {
+            prefix: "".to_string(),
+            limit: None,
+            order: None,
+        };
+        let rank_of = QueryMsg::RankOf {
+            name: Some("scores/000".to_string()),
+        };
+        let res = query(deps.as_ref(), mock_env(), top_n.clone());
+        assert_eq!(incomplete, res.unwrap_err());
+
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: None,
+            limit: Some(20),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), rank_of.clone());
+        assert_eq!(incomplete, res.unwrap_err());
+        // A page skipping keys does not complete the backfill.
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: Some("scores/040".to_string()),
+            limit: Some(100),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), top_n.clone());
+        assert_eq!(incomplete, res.unwrap_err());
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: Some("scores/019".to_string()),
+            limit: Some(100),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let top = top_n_helper(deps.as_ref(), "", None);
+        assert_eq!(DEFAULT_LIMIT as usize, top.len());
+        assert_eq!(("scores/050".to_string(), 50), top[0]);
+        assert_eq!(51, rank_of_helper(deps.as_ref(), "scores/000"));
+
+        // Disabling removes the entries in bounded batches.
+        let msg = ExecuteMsg::SetRanked {
+            path: "".to_string(),
+            ranked: false,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes[4..],
+            [
+                Attribute::new("deleted", MAX_PREFIX_DELETIONS.to_string()),
+                Attribute::new("done", "false"),
+            ]
+        );
+        let msg = ExecuteMsg::SetRanked {
+            path: "".to_string(),
+            ranked: true,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(
+            ContractError::IndexNotCleared {
+                path: "".to_string()
+            },
+            err
+        );
+
+        let disable = ExecuteMsg::SetRanked {
+            path: "".to_string(),
+            ranked: false,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), disable).unwrap();
+        assert_eq!(
+            res.attributes[4..],
+            [
+                Attribute::new("deleted", "1"),
+                Attribute::new("done", "true"),
+            ]
+        );
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let res = query(deps.as_ref(), mock_env(), top_n);
+        assert_eq!(incomplete, res.unwrap_err());
+
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::SetRanked {
+            path: "scores".to_string(),
+            ranked: true,
+        };
+        let err = execute(deps.as_mut(), mock_env(), unauth_info, msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+    }
+
+    fn aggregate_helper(
+        deps: Deps,
+        op: AggregateOp,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> AggregateResponse {
+        from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::Aggregate {
+                    prefix: "alloc/".to_string(),
+                    op,
+                    start_after,
+                    limit,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn aggregate_values_under_prefix() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let values = [
+            ("alloc/a", Primitive::Uint128(Uint128::new(30))),
+            ("alloc/b", Primitive::String("skipped".to_string())),
+            ("alloc/c", Primitive::Uint128(Uint128::new(10))),
+            ("alloc/d", Primitive::Uint128(Uint128::new(20))),
+            ("allocated", Primitive::Uint128(Uint128::new(1000))),
+        ];
+        for (name, value) in values.iter() {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name.to_string()),
+                value: value.clone(),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let expected = [
+            (AggregateOp::Sum, Some(60)),
+            (AggregateOp::Min, Some(10)),
+            (AggregateOp::Max, Some(30)),
+            (AggregateOp::Count, Some(3)),
+        ];
+        for (op, value) in expected.iter() {
+            let res = aggregate_helper(deps.as_ref(), *op, None, None);
+            assert_eq!(
+                AggregateResponse {
+                    prefix: "alloc/".to_string(),
+                    op: *op,
+                    value: value.map(Uint128::new),
+                    count: 3,
+                    next_start_after: None,
+                },
+                res
+            );
+        }
+
+        // Pages continue after the last key read.
+        let res = aggregate_helper(deps.as_ref(), AggregateOp::Sum, None, Some(2));
+        assert_eq!(Some(Uint128::new(40)), res.value);
+        assert_eq!(Some("alloc/c".to_string()), res.next_start_after);
+        let res = aggregate_helper(
+            deps.as_ref(),
+            AggregateOp::Sum,
+            res.next_start_after,
+            Some(2),
+        );
+        assert_eq!(Some(Uint128::new(20)), res.value);
+        assert_eq!(1, res.count);
+        assert_eq!(None, res.next_start_after);
+
+        let res = aggregate_helper(
+            deps.as_ref(),
+            AggregateOp::Min,
+            Some("alloc/d".to_string()),
+            None,
+        );
+        assert_eq!(None, res.value);
+        assert_eq!(0, res.count);
+    }
+
+    fn list_by_tag_helper(deps: Deps, tag: &str, start_after: Option<String>) -> Vec<String> {
+        let res: ListByTagResponse = from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::ListByTag {
+                    tag: tag.to_string(),
+                    start_after,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        res.keys
+    }
+
+    #[test]
+    fn tag_keys_and_list_by_tag() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        for name in ["a", "b", "c"].iter() {
+            let msg = ExecuteMsg::SetValue {
+                name: Some(name.to_string()),
+                value: Primitive::Bool(true),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let msg = ExecuteMsg::SetTags {
+            name: Some("b".to_string()),
+            tags: vec!["public".to_string(), "team:payments".to_string()],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "set_tags"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("name", "b"),
+                Attribute::new("tags", "public,team:payments"),
+            ]
+        );
+        for name in ["a", "c"].iter() {
+            let msg = ExecuteMsg::SetTags {
+                name: Some(name.to_string()),
+                tags: vec!["public".to_string()],
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        assert_eq!(
+            vec!["a", "b", "c"],
+            list_by_tag_helper(deps.as_ref(), "public", None)
+        );
+        assert_eq!(
+            vec!["c"],
+            list_by_tag_helper(deps.as_ref(), "public", Some("b".to_string()))
+        );
+        assert_eq!(
+            GetValueResponse {
+                name: "b".to_string(),
+                value: Primitive::Bool(true),
+                tags: vec!["public".to_string(), "team:payments".to_string()],
+                info: None,
+            },
+            query_value_helper(deps.as_ref(), Some("b".to_string()))
+        );
+
+        // Tags go away when removed or with the key.
+        let msg = ExecuteMsg::RemoveTags {
+            name: Some("b".to_string()),
+            tags: vec!["public".to_string(), "unknown".to_string()],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::DeleteValue {
+            name: Some("c".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(vec!["a"], list_by_tag_helper(deps.as_ref(), "public", None));
+        assert_eq!(
+            vec!["b"],
+            list_by_tag_helper(deps.as_ref(), "team:payments", None)
+        );
+
+        let msg = ExecuteMsg::SetTags {
+            name: Some("missing".to_string()),
+            tags: vec!["public".to_string()],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+
+        let msg = ExecuteMsg::SetTags {
+            name: Some("a".to_string()),
+            tags: vec!["".to_string()],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::InvalidTag {}, err);
+
+        let msg = ExecuteMsg::SetTags {
+            name: Some("a".to_string()),
+            tags: (0..MAX_TAGS).map(|i| format!("tag{}", i)).collect(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TooManyTags {
+                name: "a".to_string(),
+                max: MAX_TAGS
+            },
+            err
+        );
+
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::RemoveTags {
+            name: Some("a".to_string()),
+            tags: vec!["public".to_string()],
+        };
+        let err = execute(deps.as_mut(), mock_env(), unauth_info, msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+    }
+
+    #[test]
+    fn describe_keys_with_key_info() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: Some("staked".to_string()),
+            value: Primitive::Uint128(Uint128::new(1500000)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetKeyInfo {
+            name: Some("staked".to_string()),
+            description: Some("Total amount staked".to_string()),
+            unit: Some("ATOM".to_string()),
+            decimals: Some(6),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "set_key_info"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("name", "staked"),
+            ]
+        );
+
+        let key_info = KeyInfo {
+            description: Some("Total amount staked".to_string()),
+            unit: Some("ATOM".to_string()),
+            decimals: Some(6),
+        };
+        let res: KeyInfoResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::KeyInfo {
+                    name: Some("staked".to_string()),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            KeyInfoResponse {
+                name: "staked".to_string(),
+                description: key_info.description.clone(),
+                unit: key_info.unit.clone(),
+                decimals: key_info.decimals,
+            },
+            res
+        );
+
+        let res: GetValueResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetValue {
+                    name: Some("staked".to_string()),
+                    with_info: Some(true),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Some(key_info), res.info);
+        assert_eq!(
+            None,
+            query_value_helper(deps.as_ref(), Some("staked".to_string())).info
+        );
+
+        let msg = ExecuteMsg::SetKeyInfo {
+            name: Some("staked".to_string()),
+            description: None,
+            unit: None,
+            decimals: Some(MAX_DECIMALS + 1),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::InvalidDecimals { max: 38 }, err);
+
+        // Metadata is removed with the key, and cannot be set without one.
+        let msg = ExecuteMsg::DeleteValue {
+            name: Some("staked".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert!(!KEY_INFO.has(&deps.storage, "staked"));
+        let msg = ExecuteMsg::SetKeyInfo {
+            name: Some("staked".to_string()),
+            description: Some("Total amount staked".to_string()),
+            unit: None,
+            decimals: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Std(StdError::NotFound { .. })));
+
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::SetKeyInfo {
+            name: None,
+            description: Some("Anything".to_string()),
+            unit: None,
+            decimals: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), unauth_info, msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+    }
+
+    #[test]
+    fn type_locks_reject_mismatched_writes() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: Some("supply".to_string()),
+            value: Primitive::String("100".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // The stored value has to match the lock.
+        let uint_lock = TypeLock {
+            r#type: PrimitiveType::Uint128,
+            element_type: None,
+        };
+        let msg = ExecuteMsg::SetTypeLock {
+            scope: KeyScope::Key {
+                name: Some("supply".to_string()),
+            },
+            lock: Some(uint_lock.clone()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg.clone()).unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "Uint128".to_string(),
+                got: "String".to_string(),
+            },
+            err
+        );
+
+        let set_supply = ExecuteMsg::SetValue {
+            name: Some("supply".to_string()),
+            value: Primitive::Uint128(Uint128::new(100)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), set_supply).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "set_type_lock"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("name", "supply"),
+                Attribute::new("lock", "Uint128"),
+            ]
+        );
+
+        let msg = ExecuteMsg::SetValue {
+            name: Some("supply".to_string()),
+            value: Primitive::String("oops".to_string()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "Uint128".to_string(),
+                got: "String".to_string(),
+            },
+            err
+        );
+        let msg = ExecuteMsg::VecPush {
+            name: Some("supply".to_string()),
+            value: Primitive::Bool(true),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+
+        // The lock outlives the key, so a deleted key cannot come back with another type.
+        let msg = ExecuteMsg::DeleteValue {
+            name: Some("supply".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::VecPush {
+            name: Some("supply".to_string()),
+            value: Primitive::Bool(true),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "Uint128".to_string(),
+                got: "Vec".to_string(),
+            },
+            err
+        );
+        let msg = ExecuteMsg::Increment {
+            name: Some("supply".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        // Prefix locks can fix the element type of Vecs.
+        let msg = ExecuteMsg::SetTypeLock {
+            scope: KeyScope::Prefix {
+                path: "lists".to_string(),
+            },
+            lock: Some(TypeLock {
+                r#type: PrimitiveType::Vec,
+                element_type: Some(PrimitiveType::String),
+            }),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetValue {
+            name: Some("lists/a".to_string()),
+            value: Primitive::Vec(vec![
+                Primitive::String("a".to_string()),
+                Primitive::Uint128(Uint128::new(1)),
+            ]),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "Vec<String>".to_string(),
+                got: "Vec<Uint128>".to_string(),
+            },
+            err
+        );
+        let msg = ExecuteMsg::VecPush {
+            name: Some("lists/a".to_string()),
+            value: Primitive::String("a".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::VecSet {
+            name: Some("lists/a".to_string()),
+            index: 0,
+            value: Primitive::Bool(false),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "Vec<String>".to_string(),
+                got: "Vec<Bool>".to_string(),
+            },
+            err
+        );
+
+        let res: TypeLockResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetTypeLock {
+                    scope: KeyScope::Key {
+                        name: Some("supply".to_string()),
+                    },
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Some(uint_lock), res.lock);
+
+        let msg = ExecuteMsg::SetTypeLock {
+            scope: KeyScope::Key { name: None },
+            lock: Some(TypeLock {
+                r#type: PrimitiveType::Bool,
+                element_type: Some(PrimitiveType::Bool),
+            }),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::InvalidTypeLock {}, err);
+
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::SetTypeLock {
+            scope: KeyScope::Key {
+                name: Some("supply".to_string()),
+            },
+            lock: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), unauth_info, msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+    }
+
+    #[test]
+    fn constraints_are_enforced_on_every_write() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetConstraints {
+            scope: KeyScope::Key {
+                name: Some("fee".to_string()),
+            },
+            constraints: vec![Constraint::Range {
+                min: None,
+                max: Some(Uint128::new(100)),
+            }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "set_constraints"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("name", "fee"),
+                Attribute::new("constraints", "1"),
+            ]
+        );
+        let msg = ExecuteMsg::Add {
+            name: Some("fee".to_string()),
+            amount: Uint128::new(101),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::OutOfBounds {
+                name: "fee".to_string(),
+                value: Uint128::new(101)
+            },
+            err
+        );
+
+        let msg = ExecuteMsg::SetConstraints {
+            scope: KeyScope::Prefix {
+                path: "status".to_string(),
+            },
+            constraints: vec![
+                Constraint::OneOf {
+                    values: vec![
+                        Primitive::String("active".to_string()),
+                        Primitive::String("paused".to_string()),
+                    ],
+                },
+                Constraint::MaxItems { max: 2 },
+            ],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::SetConstraints {
+            scope: KeyScope::Key {
+                name: Some("status/name".to_string()),
+            },
+            constraints: vec![
+                Constraint::MaxLength { max: 6 },
+                Constraint::Pattern {
+                    pattern: "p*".to_string(),
+                },
+            ],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let set = |name: &str, value: &str| ExecuteMsg::SetValue {
+            name: Some(name.to_string()),
+            value: Primitive::String(value.to_string()),
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            set("status/a", "active"),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            set("status/a", "gone"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            ContractError::NotAllowed {
+                name: "status/a".to_string()
+            },
+            err
+        );
+        // The prefix constraints apply together with those of the key.
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            set("status/name", "active"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            ContractError::PatternMismatch {
+                name: "status/name".to_string(),
+                pattern: "p*".to_string()
+            },
+            err
+        );
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            set("status/name", "paused"),
+        )
+        .unwrap();
+        let msg = ExecuteMsg::SetConstraints {
+            scope: KeyScope::Key {
+                name: Some("status/name".to_string()),
+            },
+            constraints: vec![Constraint::MaxLength { max: 5 }],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TooLong {
+                name: "status/name".to_string(),
+                max: 5
+            },
+            err
+        );
+
+        // Vec elements and lengths are checked by the in-place operations too.
+        let push = |value: &str| ExecuteMsg::VecPush {
+            name: Some("status/list".to_string()),
+            value: Primitive::String(value.to_string()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), push("gone")).unwrap_err();
+        assert_eq!(
+            ContractError::NotAllowed {
+                name: "status/list".to_string()
+            },
+            err
+        );
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), push("active")).unwrap();
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), push("paused")).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), push("active")).unwrap_err();
+        assert_eq!(
+            ContractError::TooManyItems {
+                name: "status/list".to_string(),
+                max: 2
+            },
+            err
+        );
+        let msg = ExecuteMsg::VecSet {
+            name: Some("status/list".to_string()),
+            index: 1,
+            value: Primitive::String("active".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let res: ConstraintsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetConstraints {
+                    scope: KeyScope::Key {
+                        name: Some("fee".to_string()),
+                    },
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![Constraint::Range {
+                min: None,
+                max: Some(Uint128::new(100)),
+            }],
+            res.constraints
+        );
+
+        let msg = ExecuteMsg::SetConstraints {
+            scope: KeyScope::Key { name: None },
+            constraints: vec![Constraint::Range {
+                min: Some(Uint128::new(2)),
+                max: Some(Uint128::new(1)),
+            }],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::InvalidBounds {}, err);
+
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::SetConstraints {
+            scope: KeyScope::Key { name: None },
+            constraints: vec![],
+        };
+        let err = execute(deps.as_mut(), mock_env(), unauth_info, msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+    }
+
+    #[test]
+    fn registered_schemas_are_enforced_and_listed() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let amount = r#"{"type":"integer","maximum":1000}"#;
+        let msg = ExecuteMsg::RegisterSchema {
+            name_or_prefix: "alloc/".to_string(),
+            schema: amount.to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "register_schema"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("name_or_prefix", "alloc/"),
+            ]
+        );
+        // Annotations are accepted but not kept.
+        let codes = r#"{"type":"array","items":{"type":"string","maxLength":3}}"#;
+        let msg = ExecuteMsg::RegisterSchema {
+            name_or_prefix: "codes".to_string(),
+            schema: r#"{
+                "title": "Codes",
+                "type": "array",
+                "items": { "type": "string", "maxLength": 3 }
+            }"#
+            .to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let msg = ExecuteMsg::SetValue {
+            name: Some("alloc/a".to_string()),
+            value: Primitive::String("1".to_string()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "Uint128".to_string(),
+                got: "String".to_string(),
+            },
+            err
+        );
+        let msg = ExecuteMsg::Add {
+            name: Some("alloc/a".to_string()),
+            amount: Uint128::new(1001),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::OutOfBounds {
+                name: "alloc/a".to_string(),
+                value: Uint128::new(1001),
+            },
+            err
+        );
+
+        let push = |value: Primitive| ExecuteMsg::VecPush {
+            name: Some("codes".to_string()),
+            value,
+        };
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            push(Primitive::String("abc".to_string())),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            push(Primitive::String("abcd".to_string())),
+        )
+        .unwrap_err();
+        assert_eq!(
+            ContractError::TooManyChars {
+                name: "codes".to_string(),
+                max: 3
+            },
+            err
+        );
+        // maxLength counts characters like JSON Schema does, not bytes.
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            push(Primitive::String("😀😀😀".to_string())),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            push(Primitive::Bool(true)),
+        )
+        .unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "Vec<String>".to_string(),
+                got: "Vec<Bool>".to_string(),
+            },
+            err
+        );
+
+        let res: ListSchemasResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ListSchemas {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                SchemaResponse {
+                    name_or_prefix: "alloc/".to_string(),
+                    schema: Some(amount.to_string()),
+                },
+                SchemaResponse {
+                    name_or_prefix: "codes".to_string(),
+                    schema: Some(codes.to_string()),
+                },
+            ],
+            res.schemas
+        );
+
+        // Removing the schema lifts its rules.
+        let msg = ExecuteMsg::RemoveSchema {
+            name_or_prefix: "codes".to_string(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            push(Primitive::Bool(true)),
+        )
+        .unwrap();
+        let res: SchemaResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetSchema {
+                    name_or_prefix: "codes".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(None, res.schema);
+
+        // The current value has to match a schema registered for its key.
+        let msg = ExecuteMsg::RegisterSchema {
+            name_or_prefix: "codes".to_string(),
+            schema: r#"{"type":"string"}"#.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "String".to_string(),
+                got: "Vec".to_string(),
+            },
+            err
+        );
+        let msg = ExecuteMsg::RegisterSchema {
+            name_or_prefix: "codes".to_string(),
+            schema: r#"{"items":{}}"#.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidSchema {
+                reason: "items is only allowed for the array type".to_string()
+            },
+            err
+        );
+        let msg = ExecuteMsg::RegisterSchema {
+            name_or_prefix: "codes".to_string(),
+            schema: r#"{"minimum":"1"}"#.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidSchema {
+                reason: "minimum must be a non-negative integer".to_string()
+            },
+            err
+        );
+
+        // The target has to be a name callers could write to.
+        let register = |name_or_prefix: &str| ExecuteMsg::RegisterSchema {
+            name_or_prefix: name_or_prefix.to_string(),
+            schema: "{}".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), register("a//")).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidKey {
+                reason: "name must not start or end with a separator".to_string()
+            },
+            err
+        );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            register(&format!("{}x", SYSTEM_PREFIX)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidKey { .. }));
+        let msg = ExecuteMsg::SetKeyRules {
+            rules: KeyRules {
+                max_length: 64,
+                allowed_symbols: Some("-".to_string()),
+            },
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), register("a_b/")).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidKey {
+                reason: "name must not contain '_'".to_string()
+            },
+            err
+        );
+        // A Vec never equals one of the single values an enum lists.
+        let msg = ExecuteMsg::RegisterSchema {
+            name_or_prefix: "codes".to_string(),
+            schema: r#"{"enum":["abc"]}"#.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::NotAllowed {
+                name: "codes".to_string()
+            },
+            err
+        );
+        let msg = ExecuteMsg::RegisterSchema {
+            name_or_prefix: "codes".to_string(),
+            schema: r#"{"type":"array","enum":["abc"]}"#.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidSchema {
+                reason: "enum, minimum, maximum and maxLength of arrays belong in items"
+                    .to_string()
+            },
+            err
+        );
+
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::RemoveSchema {
+            name_or_prefix: "alloc/".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), unauth_info, msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+    }
+
+    #[test]
+    fn key_names_are_validated() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg::default();
+        let info = mock_info("creator", &[]);
+
+        // we can just call .unwrap() to assert this was a success
+        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+
+        let set = |name: &str| ExecuteMsg::SetValue {
+            name: Some(name.to_string()),
+            value: Primitive::Bool(true),
+        };
+        let invalid_key = |reason: &str| ContractError::InvalidKey {
+            reason: reason.to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), set("")).unwrap_err();
+        assert_eq!(invalid_key("name must not be empty"), err);
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), set("/a")).unwrap_err();
+        assert_eq!(
+            invalid_key("name must not start or end with a separator"),
+            err
+        );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            set(&"a".repeat(257)),
+        )
+        .unwrap_err();
+        assert_eq!(invalid_key("name is longer than 256 bytes"), err);
+
+        // The system prefix is reserved for every write, including deletes.
+        let reserved = invalid_key("names starting with $ are reserved");
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), set("$owner")).unwrap_err();
+        assert_eq!(reserved, err);
+        let msg = ExecuteMsg::Increment {
+            name: Some("$counter".to_string()),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(reserved, err);
+        let msg = ExecuteMsg::Batch {
+            ops: vec![Op::Delete {
+                name: Some("$owner".to_string()),
+            }],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(reserved, err);
+        let msg = ExecuteMsg::DeletePrefix {
+            path: "$".to_string(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(reserved, err);
+
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), set("a b")).unwrap();
+        // The default key has to follow the new rules.
+        let msg = ExecuteMsg::SetKeyRules {
+            rules: KeyRules {
+                max_length: 4,
+                allowed_symbols: None,
+            },
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(invalid_key("name is longer than 4 bytes"), err);
+        let rules = KeyRules {
+            max_length: 8,
+            allowed_symbols: Some("_".to_string()),
+        };
+        let msg = ExecuteMsg::SetKeyRules {
+            rules: rules.clone(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "set_key_rules"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("max_length", "8"),
+                Attribute::new("allowed_symbols", "_"),
+            ]
+        );
+        let config: ConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(rules, config.key_rules);
+
+        let msg = ExecuteMsg::VecPush {
+            name: Some("a b".to_string()),
+            value: Primitive::Bool(true),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(invalid_key("name must not contain ' '"), err);
+        // Keys written before the rules changed can still be deleted.
+        let msg = ExecuteMsg::DeleteValue {
+            name: Some("a b".to_string()),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), set("a/b_c")).unwrap();
+
+        // Metadata is written under the same name checks as values.
+        let metadata_writes = |name: &str| {
+            let name = Some(name.to_string());
+            vec![
+                ExecuteMsg::SetBounds {
+                    name: name.clone(),
+                    min: None,
+                    max: Some(Uint128::new(1)),
+                },
+                ExecuteMsg::SetUnique {
+                    name: name.clone(),
+                    unique: true,
+                },
+                ExecuteMsg::SetKeyInfo {
+                    name: name.clone(),
+                    description: None,
+                    unit: None,
+                    decimals: None,
+                },
+                ExecuteMsg::SetTags {
+                    name: name.clone(),
+                    tags: vec!["t".to_string()],
+                },
+                ExecuteMsg::SetTypeLock {
+                    scope: KeyScope::Key { name: name.clone() },
+                    lock: Some(TypeLock {
+                        r#type: PrimitiveType::Bool,
+                        element_type: None,
+                    }),
+                },
+                ExecuteMsg::SetConstraints {
+                    scope: KeyScope::Key { name },
+                    constraints: vec![],
+                },
+            ]
+        };
+        for msg in metadata_writes("$owner") {
+            let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+            assert_eq!(reserved, err);
+        }
+        for msg in metadata_writes("a-b") {
+            let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+            assert_eq!(invalid_key("name must not contain '-'"), err);
+        }
+        let msg = ExecuteMsg::SetTypeLock {
+            scope: KeyScope::Prefix {
+                path: "$".to_string(),
+            },
+            lock: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(reserved, err);
+        for msg in metadata_writes("a/b_c") {
+            let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::SetKeyRules {
+            rules: KeyRules::default(),
+        };
+        let err = execute(deps.as_mut(), mock_env(), unauth_info, msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+    }
+
+    #[test]
+    fn instantiate_with_owner_default_key_and_limits() {
+        let mut deps = mock_dependencies(&[]);
+
+        let limits = Limits {
+            max_batch_ops: 2,
+            max_prefix_deletions: 10,
+        };
+        let msg = InstantiateMsg {
+            owner: Some("admin".to_string()),
+            default_key: Some("main".to_string()),
+            limits: Some(limits.clone()),
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("factory", &[]);
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "instantiate"),
+                Attribute::new("owner", "admin"),
+                Attribute::new("values", "0"),
+            ]
+        );
+
+        let config: ConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(
+            ConfigResponse {
+                owner: Addr::unchecked("admin"),
+                reverse_index: false,
+                key_rules: KeyRules::default(),
+                default_key: "main".to_string(),
+                limits,
+                import_finished: false,
+                change_retention: None,
+            },
+            config
+        );
+
+        // The factory does not keep any rights.
+        let msg = ExecuteMsg::SetValue {
+            name: None,
+            value: Primitive::Bool(true),
+        };
+        let err = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+
+        let admin_info = mock_info("admin", &[]);
+        let res = execute(deps.as_mut(), mock_env(), admin_info.clone(), msg).unwrap();
+        assert_eq!(res.attributes[2], Attribute::new("name", "main"),);
+        assert_eq!("main", query_value_helper(deps.as_ref(), None).name);
+
+        let msg = ExecuteMsg::Batch {
+            ops: vec![Op::Delete { name: None }; 3],
+        };
+        let err = execute(deps.as_mut(), mock_env(), admin_info, msg).unwrap_err();
+        assert_eq!(ContractError::BatchTooLarge { max: 2 }, err);
+    }
+
+    #[test]
+    fn instantiate_rejects_invalid_config() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            default_key: Some("$default".to_string()),
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("creator", &[]);
+        let err = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidKey {
+                reason: "names starting with $ are reserved".to_string()
+            },
+            err
+        );
+
+        let msg = InstantiateMsg {
+            default_key: Some("default key".to_string()),
+            key_rules: Some(KeyRules {
+                max_length: 64,
+                allowed_symbols: Some("_".to_string()),
+            }),
+            ..InstantiateMsg::default()
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidKey {
+                reason: "name must not contain ' '".to_string()
+            },
+            err
+        );
+
+        for limits in [(0, 10), (10, 0)].iter() {
+            let msg = InstantiateMsg {
+                limits: Some(Limits {
+                    max_batch_ops: limits.0,
+                    max_prefix_deletions: limits.1,
+                }),
+                ..InstantiateMsg::default()
+            };
+            let err = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+            assert_eq!(ContractError::InvalidLimits {}, err);
+        }
+    }
+
+    #[test]
+    fn instantiate_with_initial_values() {
+        let mut deps = mock_dependencies(&[]);
+
+        let msg = InstantiateMsg {
+            default_key: Some("main".to_string()),
+            values: Some(vec![
+                InitialValue {
+                    name: None,
+                    value: Primitive::Bool(true),
+                },
+                InitialValue {
+                    name: Some("fees/swap".to_string()),
+                    value: Primitive::Uint128(Uint128::new(30)),
+                },
+            ]),
+            reverse_index: Some(true),
+            ..InstantiateMsg::default()
+        };
+        let info = mock_info("creator", &[]);
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "instantiate"),
+                Attribute::new("owner", "creator"),
+                Attribute::new("values", "2"),
+            ]
+        );
+
+        assert_eq!(
+            Primitive::Bool(true),
+            query_value_helper(deps.as_ref(), None).value
+        );
+        assert_eq!(
+            Primitive::Uint128(Uint128::new(30)),
+            query_value_helper(deps.as_ref(), Some("fees/swap".to_string())).value
+        );
+        // The indexes are maintained for initial values too.
+        assert_eq!(
+            vec!["fees/swap"],
+            find_keys_helper(deps.as_ref(), Primitive::Uint128(Uint128::new(30)))
+        );
 
-        // we can just call .unwrap() to assert this was a success
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        // A single invalid value rejects the whole instantiation.
+        let mut deps = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            values: Some(vec![
+                InitialValue {
+                    name: Some("a".to_string()),
+                    value: Primitive::Bool(true),
+                },
+                InitialValue {
+                    name: Some("b".to_string()),
+                    value: Primitive::Vec(vec![Primitive::Vec(vec![])]),
+                },
+            ]),
+            ..InstantiateMsg::default()
+        };
+        let err = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::InvalidPrimitive {}, err);
 
-        let msg = ExecuteMsg::SetValue {
-            name: None,
-            value: Primitive::Vec(vec![Primitive::Vec(vec![])]),
+        let msg = InstantiateMsg {
+            values: Some(vec![InitialValue {
+                name: Some("$seed".to_string()),
+                value: Primitive::Bool(true),
+            }]),
+            ..InstantiateMsg::default()
         };
-        let res: Result<Response, ContractError> =
-            execute(deps.as_mut(), mock_env(), info.clone(), msg);
-        assert_eq!(ContractError::InvalidPrimitive {}, res.unwrap_err());
+        let err = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidKey {
+                reason: "names starting with $ are reserved".to_string()
+            },
+            err
+        );
     }
 
-    #[test]
-    fn delete_value_with_name() {
-        let mut deps = mock_dependencies(&[]);
+    fn export_helper(deps: Deps, start_after: Option<String>) -> ExportStateResponse {
+        from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::ExportState {
+                    start_after,
+                    limit: Some(2),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn export_metadata_helper(
+        deps: Deps,
+        start_after: Option<MetadataCursor>,
+    ) -> ExportMetadataResponse {
+        from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::ExportMetadata {
+                    start_after,
+                    limit: Some(3),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
 
-        let msg = InstantiateMsg {};
+    #[test]
+    fn export_and_import_state() {
+        let mut source = mock_dependencies(&[]);
         let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            reverse_index: Some(true),
+            default_key: Some("main".to_string()),
+            key_rules: Some(KeyRules {
+                max_length: 64,
+                allowed_symbols: Some("_".to_string()),
+            }),
+            limits: Some(Limits {
+                max_batch_ops: 5,
+                max_prefix_deletions: 7,
+            }),
+            change_retention: Some(100),
+            ..InstantiateMsg::default()
+        };
+        let _res = instantiate(source.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
-        // we can just call .unwrap() to assert this was a success
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let large: Vec<Primitive> = (0..VEC_CHUNK_THRESHOLD as u128 + 1)
+            .map(|i| Primitive::Uint128(Uint128::new(i)))
+            .collect();
+        let msgs = vec![
+            ExecuteMsg::SetValue {
+                name: Some("fee".to_string()),
+                value: Primitive::Uint128(Uint128::new(30)),
+            },
+            ExecuteMsg::SetBounds {
+                name: Some("fee".to_string()),
+                min: None,
+                max: Some(Uint128::new(100)),
+            },
+            ExecuteMsg::SetKeyInfo {
+                name: Some("fee".to_string()),
+                description: Some("Swap fee".to_string()),
+                unit: Some("bps".to_string()),
+                decimals: None,
+            },
+            ExecuteMsg::SetValue {
+                name: Some("list".to_string()),
+                value: Primitive::Vec(large.clone()),
+            },
+            ExecuteMsg::SetUnique {
+                name: Some("list".to_string()),
+                unique: true,
+            },
+            ExecuteMsg::SetValue {
+                name: Some("owner".to_string()),
+                value: Primitive::String("alice".to_string()),
+            },
+            ExecuteMsg::SetTags {
+                name: Some("owner".to_string()),
+                tags: vec!["public".to_string()],
+            },
+            ExecuteMsg::SetTypeLock {
+                scope: KeyScope::Key {
+                    name: Some("fee".to_string()),
+                },
+                lock: Some(TypeLock {
+                    r#type: PrimitiveType::Uint128,
+                    element_type: None,
+                }),
+            },
+            ExecuteMsg::SetConstraints {
+                scope: KeyScope::Prefix {
+                    path: "list".to_string(),
+                },
+                constraints: vec![Constraint::MaxItems { max: 1000 }],
+            },
+            ExecuteMsg::RegisterSchema {
+                name_or_prefix: "fee".to_string(),
+                schema: r#"{"type":"integer","maximum":1000}"#.to_string(),
+            },
+            ExecuteMsg::SetRanked {
+                path: "scores".to_string(),
+                ranked: true,
+            },
+        ];
+        for msg in msgs {
+            let _res = execute(source.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+
+        let first_metadata = export_metadata_helper(source.as_ref(), None);
+        assert_eq!(
+            vec![
+                Metadata::TypeLock {
+                    scope: KeyScope::Key {
+                        name: Some("fee".to_string())
+                    },
+                    lock: TypeLock {
+                        r#type: PrimitiveType::Uint128,
+                        element_type: None,
+                    },
+                },
+                Metadata::Constraints {
+                    scope: KeyScope::Prefix {
+                        path: "list/".to_string()
+                    },
+                    constraints: vec![Constraint::MaxItems { max: 1000 }],
+                },
+                Metadata::Schema {
+                    name_or_prefix: "fee".to_string(),
+                    schema: r#"{"type":"integer","maximum":1000}"#.to_string(),
+                },
+            ],
+            first_metadata.metadata
+        );
+        assert_eq!(
+            Some(MetadataCursor {
+                kind: MetadataKind::Schema,
+                key: "fee".to_string(),
+            }),
+            first_metadata.next_start_after
+        );
+        let second_metadata =
+            export_metadata_helper(source.as_ref(), first_metadata.next_start_after.clone());
+        assert_eq!(
+            vec![Metadata::Ranked {
+                path: "scores/".to_string()
+            }],
+            second_metadata.metadata
+        );
+        assert_eq!(None, second_metadata.next_start_after);
+
+        let first = export_helper(source.as_ref(), None);
+        assert_eq!(Addr::unchecked("creator"), first.config.owner);
+        assert_eq!(
+            ExportEntry {
+                name: "fee".to_string(),
+                value: Primitive::Uint128(Uint128::new(30)),
+                tags: vec![],
+                info: Some(KeyInfo {
+                    description: Some("Swap fee".to_string()),
+                    unit: Some("bps".to_string()),
+                    decimals: None,
+                }),
+                bounds: Some(Bounds {
+                    min: None,
+                    max: Some(Uint128::new(100)),
+                }),
+                unique: false,
+            },
+            first.entries[0]
+        );
+        assert_eq!(Primitive::Vec(large), first.entries[1].value);
+        assert_eq!(Some("list".to_string()), first.next_start_after);
+        let second = export_helper(source.as_ref(), first.next_start_after.clone());
+        assert_eq!(1, second.entries.len());
+        assert_eq!(None, second.next_start_after);
 
+        let mut target = mock_dependencies(&[]);
+        let _res = instantiate(
+            target.as_mut(),
+            mock_env(),
+            info.clone(),
+            InstantiateMsg::default(),
+        )
+        .unwrap();
+        let msg = ExecuteMsg::ImportConfig {
+            config: first.config.clone(),
+        };
+        let _res = execute(target.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        for page in [&first_metadata, &second_metadata].iter() {
+            let msg = ExecuteMsg::ImportMetadata {
+                metadata: page.metadata.clone(),
+            };
+            let _res = execute(target.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        assert_eq!(
+            first_metadata,
+            export_metadata_helper(target.as_ref(), None)
+        );
+        // Existing keys are replaced together with their metadata.
         let msg = ExecuteMsg::SetValue {
-            name: Some("test1".to_string()),
-            value: Primitive::String("value1".to_string()),
+            name: Some("owner".to_string()),
+            value: Primitive::Bool(false),
+        };
+        let _res = execute(target.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        for page in [&first, &second].iter() {
+            let msg = ExecuteMsg::ImportState {
+                entries: page.entries.clone(),
+            };
+            let res = execute(target.as_mut(), mock_env(), info.clone(), msg).unwrap();
+            assert_eq!(
+                res.attributes[2],
+                Attribute::new("imported", page.entries.len().to_string())
+            );
+        }
+        assert_eq!(first, export_helper(target.as_ref(), None));
+        assert_eq!(
+            second,
+            export_helper(target.as_ref(), first.next_start_after)
+        );
+        assert_eq!(
+            vec!["owner"],
+            list_by_tag_helper(target.as_ref(), "public", None)
+        );
+        assert_eq!(
+            vec!["owner"],
+            find_keys_helper(target.as_ref(), Primitive::String("alice".to_string()))
+        );
+
+        let msg = ExecuteMsg::FinishImport {};
+        let _res = execute(target.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let config: ConfigResponse =
+            from_binary(&query(target.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert!(config.import_finished);
+        let msg = ExecuteMsg::ImportState {
+            entries: second.entries.clone(),
+        };
+        let err = execute(target.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::ImportFinished {}, err);
+        let msg = ExecuteMsg::ImportMetadata {
+            metadata: second_metadata.metadata,
+        };
+        let err = execute(target.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::ImportFinished {}, err);
+        let msg = ExecuteMsg::ImportConfig {
+            config: first.config.clone(),
+        };
+        let err = execute(target.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert_eq!(ContractError::ImportFinished {}, err);
+
+        let unauth_info = mock_info("anyone", &[]);
+        let msg = ExecuteMsg::ImportState {
+            entries: second.entries,
         };
+        let err = execute(source.as_mut(), mock_env(), unauth_info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+        let msg = ExecuteMsg::ImportConfig {
+            config: first.config,
+        };
+        let err = execute(source.as_mut(), mock_env(), unauth_info, msg).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+    }
+
+    #[test]
+    fn imports_are_validated() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            InstantiateMsg::default(),
+        )
+        .unwrap();
+
+        let entry = |value: Primitive| ExportEntry {
+            name: "key".to_string(),
+            value,
+            tags: vec![],
+            info: None,
+            bounds: None,
+            unique: false,
+        };
+        let number = Primitive::Uint128(Uint128::new(5));
+        let import = |entry: ExportEntry| ExecuteMsg::ImportState {
+            entries: vec![entry],
+        };
+        let cases = vec![
+            (
+                ExportEntry {
+                    bounds: Some(Bounds {
+                        min: Some(Uint128::new(2)),
+                        max: Some(Uint128::new(1)),
+                    }),
+                    ..entry(number.clone())
+                },
+                ContractError::InvalidBounds {},
+            ),
+            (
+                ExportEntry {
+                    bounds: Some(Bounds {
+                        min: None,
+                        max: Some(Uint128::new(4)),
+                    }),
+                    ..entry(number.clone())
+                },
+                ContractError::OutOfBounds {
+                    name: "key".to_string(),
+                    value: Uint128::new(5),
+                },
+            ),
+            (
+                ExportEntry {
+                    unique: true,
+                    ..entry(Primitive::Vec(vec![number.clone(), number.clone()]))
+                },
+                ContractError::DuplicateElement {
+                    name: "key".to_string(),
+                },
+            ),
+            (
+                ExportEntry {
+                    info: Some(KeyInfo {
+                        description: None,
+                        unit: None,
+                        decimals: Some(MAX_DECIMALS + 1),
+                    }),
+                    ..entry(number.clone())
+                },
+                ContractError::InvalidDecimals { max: MAX_DECIMALS },
+            ),
+            (
+                ExportEntry {
+                    tags: vec!["".to_string()],
+                    ..entry(number.clone())
+                },
+                ContractError::InvalidTag {},
+            ),
+            (
+                ExportEntry {
+                    tags: (0..=MAX_TAGS).map(|i| i.to_string()).collect(),
+                    ..entry(number.clone())
+                },
+                ContractError::TooManyTags {
+                    name: "key".to_string(),
+                    max: MAX_TAGS,
+                },
+            ),
+        ];
+        for (entry, expected) in cases {
+            let err = execute(deps.as_mut(), mock_env(), info.clone(), import(entry)).unwrap_err();
+            assert_eq!(expected, err);
+        }
+        // Repeated tags only count once.
+        let msg = import(ExportEntry {
+            tags: vec!["a".to_string(); MAX_TAGS + 1],
+            ..entry(number.clone())
+        });
         let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
 
-        let query_res: GetValueResponse =
-            query_value_helper(deps.as_ref(), Some("test1".to_string()));
+        let msg = ExecuteMsg::ImportMetadata {
+            metadata: vec![Metadata::Schema {
+                name_or_prefix: "key".to_string(),
+                schema: r#"{"type":"string"}"#.to_string(),
+            }],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::TypeMismatch {
+                expected: "String".to_string(),
+                got: "Uint128".to_string(),
+            },
+            err
+        );
+        let msg = ExecuteMsg::ImportMetadata {
+            metadata: vec![Metadata::TypeLock {
+                scope: KeyScope::Prefix {
+                    path: "$".to_string(),
+                },
+                lock: TypeLock {
+                    r#type: PrimitiveType::Bool,
+                    element_type: None,
+                },
+            }],
+        };
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(
+            ContractError::InvalidKey {
+                reason: "names starting with $ are reserved".to_string()
+            },
+            err
+        );
+
+        let config: ConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        let import_config = |config: ConfigResponse| ExecuteMsg::ImportConfig { config };
+        let msg = import_config(ConfigResponse {
+            limits: Limits {
+                max_batch_ops: 0,
+                max_prefix_deletions: 1,
+            },
+            ..config.clone()
+        });
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::InvalidLimits {}, err);
+        let msg = import_config(ConfigResponse {
+            default_key: "$default".to_string(),
+            ..config.clone()
+        });
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidKey { .. }));
+        let msg = import_config(ConfigResponse {
+            reverse_index: true,
+            ..config.clone()
+        });
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        assert_eq!(ContractError::StoreNotEmpty {}, err);
+        // The owner is kept.
+        let msg = import_config(ConfigResponse {
+            owner: Addr::unchecked("someone"),
+            change_retention: Some(10),
+            ..config
+        });
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let config: ConfigResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Config {}).unwrap()).unwrap();
+        assert_eq!(Addr::unchecked("creator"), config.owner);
+        assert_eq!(Some(10), config.change_retention);
+    }
+
+    fn diff_helper(deps: Deps, label: &str) -> Vec<SnapshotDiff> {
+        let mut changes = vec![];
+        let mut start_after = None;
+        loop {
+            let res: DiffSnapshotResponse = from_binary(
+                &query(
+                    deps,
+                    mock_env(),
+                    QueryMsg::DiffSnapshot {
+                        label: label.to_string(),
+                        start_after,
+                        limit: Some(2),
+                    },
+                )
+                .unwrap(),
+            )
+            .unwrap();
+            changes.extend(res.changes);
+            match res.next_start_after {
+                Some(cursor) => start_after = Some(cursor),
+                None => return changes,
+            }
+        }
+    }
+
+    fn restore_helper(deps: &mut OwnedDeps<MockStorage, MockApi, MockQuerier>, label: &str) -> u32 {
+        let info = mock_info("creator", &[]);
+        let mut calls = 0;
+        let mut start_after = None;
+        loop {
+            let msg = ExecuteMsg::RestoreSnapshot {
+                label: label.to_string(),
+                start_after,
+                limit: Some(2),
+            };
+            let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+            calls += 1;
+            let data: RestoreSnapshotResponse = from_binary(&res.data.unwrap()).unwrap();
+            match data.next_start_after {
+                Some(cursor) => start_after = Some(cursor),
+                None => return calls,
+            }
+        }
+    }
+
+    #[test]
+    fn snapshots() {
+        let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let _res = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            InstantiateMsg::default(),
+        )
+        .unwrap();
+
+        let large: Vec<Primitive> = (0..VEC_CHUNK_THRESHOLD as u128 + 1)
+            .map(|i| Primitive::Uint128(Uint128::new(i)))
+            .collect();
+        let set = |name: &str, value: Primitive| ExecuteMsg::SetValue {
+            name: Some(name.to_string()),
+            value,
+        };
+        let snapshot = |label: &str| ExecuteMsg::CreateSnapshot {
+            label: label.to_string(),
+        };
+        let msgs = vec![
+            set("a", Primitive::Uint128(Uint128::new(1))),
+            set("b", Primitive::String("x".to_string())),
+            set("list", Primitive::Vec(large.clone())),
+            snapshot("before"),
+            set("a", Primitive::Uint128(Uint128::new(2))),
+            ExecuteMsg::DeleteValue {
+                name: Some("b".to_string()),
+            },
+            set("c", Primitive::Bool(true)),
+            ExecuteMsg::VecPush {
+                name: Some("list".to_string()),
+                value: Primitive::Uint128(Uint128::new(100)),
+            },
+            snapshot("after"),
+            set("a", Primitive::Uint128(Uint128::new(3))),
+            set("c", Primitive::Bool(false)),
+        ];
+        for msg in msgs {
+            execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
 
+        let err = execute(deps.as_mut(), mock_env(), info.clone(), snapshot("after")).unwrap_err();
         assert_eq!(
-            GetValueResponse {
-                name: "test1".to_string(),
-                value: Primitive::String("value1".to_string())
+            ContractError::SnapshotExists {
+                label: "after".to_string()
             },
-            query_res
+            err
         );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            snapshot("other"),
+        )
+        .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
 
-        let msg = ExecuteMsg::DeleteValue {
-            name: Some("test1".to_string()),
+        let res: ListSnapshotsResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::ListSnapshots {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let env = mock_env();
+        assert_eq!(
+            vec![
+                Snapshot {
+                    id: 1,
+                    label: "before".to_string(),
+                    height: env.block.height,
+                    time: env.block.time,
+                },
+                Snapshot {
+                    id: 2,
+                    label: "after".to_string(),
+                    height: env.block.height,
+                    time: env.block.time,
+                },
+            ],
+            res.snapshots
+        );
+
+        let mut pushed = large.clone();
+        pushed.push(Primitive::Uint128(Uint128::new(100)));
+        let diff = |name: &str, snapshot_value, current_value| SnapshotDiff {
+            name: name.to_string(),
+            snapshot_value,
+            current_value,
         };
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("method", "delete_value")
-                .add_attribute("sender", "creator")
-                .add_attribute("name", "test1")
+            vec![
+                diff(
+                    "a",
+                    Some(Primitive::Uint128(Uint128::new(1))),
+                    Some(Primitive::Uint128(Uint128::new(3)))
+                ),
+                diff("b", Some(Primitive::String("x".to_string())), None),
+                diff("c", None, Some(Primitive::Bool(false))),
+                diff(
+                    "list",
+                    Some(Primitive::Vec(large.clone())),
+                    Some(Primitive::Vec(pushed.clone()))
+                ),
+            ],
+            diff_helper(deps.as_ref(), "before")
         );
-        let query_res = &query(
+        assert_eq!(
+            vec![
+                diff(
+                    "a",
+                    Some(Primitive::Uint128(Uint128::new(2))),
+                    Some(Primitive::Uint128(Uint128::new(3)))
+                ),
+                diff(
+                    "c",
+                    Some(Primitive::Bool(true)),
+                    Some(Primitive::Bool(false))
+                ),
+            ],
+            diff_helper(deps.as_ref(), "after")
+        );
+
+        // Six changes were recorded, undone two at a time until a call finds none left.
+        assert_eq!(4, restore_helper(&mut deps, "before"));
+        assert_eq!(
+            Vec::<SnapshotDiff>::new(),
+            diff_helper(deps.as_ref(), "before")
+        );
+        assert_eq!(
+            Primitive::Uint128(Uint128::new(1)),
+            query_value_helper(deps.as_ref(), Some("a".to_string())).value
+        );
+        assert_eq!(
+            Primitive::String("x".to_string()),
+            query_value_helper(deps.as_ref(), Some("b".to_string())).value
+        );
+        assert_eq!(
+            Primitive::Vec(large),
+            query_value_helper(deps.as_ref(), Some("list".to_string())).value
+        );
+        let err = query(
             deps.as_ref(),
             mock_env(),
             QueryMsg::GetValue {
-                name: Some("test1".to_string()),
+                name: Some("c".to_string()),
+                with_info: None,
             },
+        )
+        .unwrap_err();
+        assert!(matches!(err, StdError::NotFound { .. }));
+
+        // The restore itself can be undone by restoring a later snapshot.
+        restore_helper(&mut deps, "after");
+        assert_eq!(
+            Primitive::Uint128(Uint128::new(2)),
+            query_value_helper(deps.as_ref(), Some("a".to_string())).value
+        );
+        assert_eq!(
+            Primitive::Bool(true),
+            query_value_helper(deps.as_ref(), Some("c".to_string())).value
+        );
+        assert_eq!(
+            Primitive::Vec(pushed),
+            query_value_helper(deps.as_ref(), Some("list".to_string())).value
+        );
+        assert_eq!(
+            Vec::<SnapshotDiff>::new(),
+            diff_helper(deps.as_ref(), "after")
         );
-        assert!(query_res.is_err());
     }
 
     #[test]
-    fn delete_value_without_name() {
+    fn snapshots_are_deleted_oldest_first() {
         let mut deps = mock_dependencies(&[]);
-
-        let msg = InstantiateMsg {};
         let info = mock_info("creator", &[]);
+        let _res = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            InstantiateMsg::default(),
+        )
+        .unwrap();
 
-        // we can just call .unwrap() to assert this was a success
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
-
-        let msg = ExecuteMsg::SetValue {
-            name: None,
-            value: Primitive::String("value1".to_string()),
+        let set = |name: &str, value: u128| ExecuteMsg::SetValue {
+            name: Some(name.to_string()),
+            value: Primitive::Uint128(Uint128::new(value)),
         };
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let snapshot = |label: &str| ExecuteMsg::CreateSnapshot {
+            label: label.to_string(),
+        };
+        let delete = |label: Option<&str>, limit| ExecuteMsg::DeleteSnapshot {
+            label: label.map(String::from),
+            limit,
+        };
+        let msgs = vec![
+            set("a", 1),
+            snapshot("first"),
+            set("a", 2),
+            set("b", 1),
+            snapshot("second"),
+            set("a", 3),
+        ];
+        for msg in msgs {
+            execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
 
-        let query_res: GetValueResponse = query_value_helper(deps.as_ref(), None);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            delete(Some("second"), None),
+        )
+        .unwrap_err();
+        assert_eq!(
+            ContractError::NotOldestSnapshot {
+                label: "second".to_string()
+            },
+            err
+        );
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            delete(Some("first"), None),
+        )
+        .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
 
+        // The two changes recorded for the first snapshot are removed one per call.
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            delete(Some("first"), Some(1)),
+        )
+        .unwrap();
         assert_eq!(
-            GetValueResponse {
-                name: DEFAULT_KEY.to_string(),
-                value: Primitive::String("value1".to_string())
+            res.attributes,
+            vec![
+                Attribute::new("method", "delete_snapshot"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("label", "first"),
+                Attribute::new("deleted", "1"),
+                Attribute::new("done", "false"),
+            ]
+        );
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), delete(None, None)).unwrap();
+        assert_eq!(
+            res.attributes[2..],
+            [
+                Attribute::new("deleted", "1"),
+                Attribute::new("done", "true"),
+            ]
+        );
+        assert!(query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::DiffSnapshot {
+                label: "first".to_string(),
+                start_after: None,
+                limit: None,
             },
-            query_res
+        )
+        .is_err());
+        // Later snapshots keep their changes.
+        assert_eq!(
+            vec![SnapshotDiff {
+                name: "a".to_string(),
+                snapshot_value: Some(Primitive::Uint128(Uint128::new(2))),
+                current_value: Some(Primitive::Uint128(Uint128::new(3))),
+            }],
+            diff_helper(deps.as_ref(), "second")
         );
 
-        let msg = ExecuteMsg::DeleteValue { name: None };
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        // Without snapshots left, changes are no longer recorded.
+        let _res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            delete(Some("second"), None),
+        )
+        .unwrap();
+        let _res = execute(deps.as_mut(), mock_env(), info.clone(), set("c", 1)).unwrap();
         assert_eq!(
-            res,
-            Response::new()
-                .add_attribute("method", "delete_value")
-                .add_attribute("sender", "creator")
-                .add_attribute("name", DEFAULT_KEY)
+            0,
+            SNAPSHOT_CHANGES
+                .range(&deps.storage, None, None, Order::Ascending)
+                .count()
         );
-        let query_res = &query(deps.as_ref(), mock_env(), QueryMsg::GetValue { name: None });
-        assert!(query_res.is_err());
+        assert_eq!(
+            0,
+            SNAPSHOT_CHANGED_KEYS
+                .range(&deps.storage, None, None, Order::Ascending)
+                .count()
+        );
+
+        // Labels can be reused, ids are not.
+        let res = execute(deps.as_mut(), mock_env(), info, snapshot("first")).unwrap();
+        assert_eq!(Attribute::new("id", "3"), res.attributes[3]);
+    }
+
+    fn changes_helper(deps: Deps, sequence: u64) -> ChangesSinceResponse {
+        from_binary(
+            &query(
+                deps,
+                mock_env(),
+                QueryMsg::ChangesSince {
+                    sequence,
+                    limit: Some(3),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap()
     }
 
     #[test]
-    fn non_creator_cannot_set_value() {
+    fn change_log() {
         let mut deps = mock_dependencies(&[]);
+        let info = mock_info("creator", &[]);
+        let msg = InstantiateMsg {
+            values: Some(vec![InitialValue {
+                name: Some("a".to_string()),
+                value: Primitive::Bool(true),
+            }]),
+            change_retention: Some(10),
+            ..InstantiateMsg::default()
+        };
+        let mut env = mock_env();
+        let start = env.block.height;
+        let _res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        env.block.height += 5;
+        let msgs = vec![
+            ExecuteMsg::VecPush {
+                name: Some("list".to_string()),
+                value: Primitive::Uint128(Uint128::new(1)),
+            },
+            // Neither of these changes anything, so they are not logged.
+            ExecuteMsg::SetAdd {
+                name: Some("list".to_string()),
+                value: Primitive::Uint128(Uint128::new(1)),
+            },
+            ExecuteMsg::DeleteValue {
+                name: Some("missing".to_string()),
+            },
+            ExecuteMsg::DeleteValue {
+                name: Some("a".to_string()),
+            },
+        ];
+        for msg in msgs {
+            execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        }
+        env.block.height += 10;
+        let msg = ExecuteMsg::Increment {
+            name: Some("count".to_string()),
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+        let change = |sequence, height, name: &str, op| Change {
+            sequence,
+            height,
+            name: name.to_string(),
+            op,
+            writer: Addr::unchecked("creator"),
+        };
+        assert_eq!(
+            ChangesSinceResponse {
+                changes: vec![
+                    change(1, start, "a", ChangeOp::Set),
+                    change(2, start + 5, "list", ChangeOp::Set),
+                    change(3, start + 5, "a", ChangeOp::Delete),
+                ],
+                latest_sequence: 4,
+            },
+            changes_helper(deps.as_ref(), 0)
+        );
+        assert_eq!(
+            vec![change(4, start + 15, "count", ChangeOp::Set)],
+            changes_helper(deps.as_ref(), 3).changes
+        );
+
+        // Only the change made more than 10 blocks ago is pruned.
+        let msg = ExecuteMsg::PruneChanges { limit: None };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(
+            res.attributes,
+            vec![
+                Attribute::new("method", "prune_changes"),
+                Attribute::new("sender", "creator"),
+                Attribute::new("pruned", "1"),
+                Attribute::new("done", "true"),
+            ]
+        );
+        let res = changes_helper(deps.as_ref(), 0);
+        assert_eq!(2, res.changes[0].sequence);
+        assert_eq!(4, res.latest_sequence);
 
-        let msg = InstantiateMsg {};
+        // A retention longer than the chain can be old keeps everything.
+        let msg = ExecuteMsg::SetChangeRetention {
+            blocks: Some(u64::MAX),
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        let msg = ExecuteMsg::PruneChanges { limit: None };
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        assert_eq!(Attribute::new("pruned", "0"), res.attributes[2]);
+
+        let msg = ExecuteMsg::SetChangeRetention { blocks: None };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+        env.block.height += 100;
+        let msg = ExecuteMsg::PruneChanges { limit: None };
+        let res = execute(deps.as_mut(), env, info, msg).unwrap();
+        assert_eq!(Attribute::new("pruned", "0"), res.attributes[2]);
+        assert_eq!(3, changes_helper(deps.as_ref(), 0).changes.len());
+    }
+
+    fn state_root_helper(deps: Deps) -> Binary {
+        let res: StateRootResponse =
+            from_binary(&query(deps, mock_env(), QueryMsg::StateRoot {}).unwrap()).unwrap();
+        res.root
+    }
+
+    #[test]
+    fn state_root_commits_to_values() {
         let info = mock_info("creator", &[]);
+        let mut first = mock_dependencies(&[]);
+        let mut second = mock_dependencies(&[]);
+        for deps in [&mut first, &mut second].iter_mut() {
+            let msg = InstantiateMsg::default();
+            instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        assert_eq!(Binary::from([0; 32]), state_root_helper(first.as_ref()));
 
-        // we can just call .unwrap() to assert this was a success
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let large: Vec<Primitive> = (0..VEC_CHUNK_THRESHOLD as u128 + 1)
+            .map(|i| Primitive::Uint128(Uint128::new(i)))
+            .collect();
+        let mut pushed = large.clone();
+        pushed.push(Primitive::Bool(true));
+        let first_msgs = vec![
+            ExecuteMsg::SetValue {
+                name: Some("a".to_string()),
+                value: Primitive::Uint128(Uint128::new(1)),
+            },
+            ExecuteMsg::SetValue {
+                name: Some("list".to_string()),
+                value: Primitive::Vec(large),
+            },
+            ExecuteMsg::VecInsert {
+                name: Some("list".to_string()),
+                index: 1,
+                value: Primitive::Bool(false),
+            },
+            ExecuteMsg::VecSet {
+                name: Some("list".to_string()),
+                index: 5,
+                value: Primitive::Bool(false),
+            },
+            ExecuteMsg::VecRemove {
+                name: Some("list".to_string()),
+                index: 1,
+            },
+            ExecuteMsg::VecSet {
+                name: Some("list".to_string()),
+                index: 4,
+                value: Primitive::Uint128(Uint128::new(4)),
+            },
+            ExecuteMsg::VecPush {
+                name: Some("list".to_string()),
+                value: Primitive::Bool(true),
+            },
+            ExecuteMsg::SetValue {
+                name: Some("b".to_string()),
+                value: Primitive::String("x".to_string()),
+            },
+        ];
+        for msg in first_msgs {
+            execute(first.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        // The same values written in another order, with detours.
+        let second_msgs = vec![
+            ExecuteMsg::SetValue {
+                name: Some("b".to_string()),
+                value: Primitive::String("x".to_string()),
+            },
+            ExecuteMsg::SetValue {
+                name: Some("c".to_string()),
+                value: Primitive::Bool(false),
+            },
+            ExecuteMsg::Add {
+                name: Some("a".to_string()),
+                amount: Uint128::new(1),
+            },
+            ExecuteMsg::SetValue {
+                name: Some("list".to_string()),
+                value: Primitive::Vec(pushed),
+            },
+            ExecuteMsg::DeleteValue {
+                name: Some("c".to_string()),
+            },
+        ];
+        for msg in second_msgs {
+            execute(second.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        }
+        let root = state_root_helper(first.as_ref());
+        assert_ne!(Binary::from([0; 32]), root);
+        assert_eq!(root, state_root_helper(second.as_ref()));
 
-        let user1 = mock_info("user1", &[]);
-        let msg = ExecuteMsg::SetValue {
-            name: Some("test1".to_string()),
-            value: Primitive::String("value1".to_string()),
+        let msg = ExecuteMsg::VecPop {
+            name: Some("list".to_string()),
         };
-        let res: Result<Response, ContractError> =
-            execute(deps.as_mut(), mock_env(), user1.clone(), msg);
-        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(second.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_ne!(root, state_root_helper(second.as_ref()));
+
+        // Values stored before the tree existed are added by RebuildIndexes.
+        let mut legacy = mock_dependencies(&[]);
+        let msg = InstantiateMsg::default();
+        instantiate(legacy.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        LEGACY_DATA
+            .save(
+                &mut legacy.storage,
+                "a",
+                &Primitive::Uint128(Uint128::new(1)),
+            )
+            .unwrap();
+        let mut single = mock_dependencies(&[]);
+        let msg = InstantiateMsg {
+            values: Some(vec![InitialValue {
+                name: Some("a".to_string()),
+                value: Primitive::Uint128(Uint128::new(1)),
+            }]),
+            ..InstantiateMsg::default()
+        };
+        instantiate(single.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        assert_ne!(
+            state_root_helper(single.as_ref()),
+            state_root_helper(legacy.as_ref())
+        );
+        let msg = ExecuteMsg::RebuildIndexes {
+            start_after: None,
+            limit: None,
+        };
+        execute(legacy.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            state_root_helper(single.as_ref()),
+            state_root_helper(legacy.as_ref())
+        );
     }
 
     #[test]
-    fn non_creator_cannot_delete_value() {
+    fn value_with_proof() {
         let mut deps = mock_dependencies(&[]);
-
-        let msg = InstantiateMsg {};
         let info = mock_info("creator", &[]);
+        let values = (0..5)
+            .map(|i| InitialValue {
+                name: Some(format!("key{}", i)),
+                value: Primitive::Uint128(Uint128::new(i)),
+            })
+            .collect();
+        let msg = InstantiateMsg {
+            default_key: Some("key3".to_string()),
+            values: Some(values),
+            ..InstantiateMsg::default()
+        };
+        instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // we can just call .unwrap() to assert this was a success
-        let _res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let res: GetValueWithProofResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::GetValueWithProof { name: None },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!("key3", res.name);
+        assert_eq!(Primitive::Uint128(Uint128::new(3)), res.value);
+        assert_eq!(state_root_helper(deps.as_ref()), res.root);
+        assert!(merkle::verify(&res.root, &res.name, &res.value, &res.proof).unwrap());
+        let forged = Primitive::Uint128(Uint128::new(4));
+        assert!(!merkle::verify(&res.root, &res.name, &forged, &res.proof).unwrap());
 
-        let msg = ExecuteMsg::SetValue {
-            name: None,
-            value: Primitive::String("value1".to_string()),
-        };
-        let _res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap();
+        let err = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetValueWithProof {
+                name: Some("missing".to_string()),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, StdError::NotFound { .. }));
 
-        let user1 = mock_info("user1", &[]);
-        let msg = ExecuteMsg::DeleteValue { name: None };
-        let res: Result<Response, ContractError> =
-            execute(deps.as_mut(), mock_env(), user1.clone(), msg);
-        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        // Legacy values can only be proven once RebuildIndexes added them to the tree.
+        LEGACY_DATA
+            .save(&mut deps.storage, "legacy", &Primitive::Bool(true))
+            .unwrap();
+        let err = query(
+            deps.as_ref(),
+            mock_env(),
+            QueryMsg::GetValueWithProof {
+                name: Some("legacy".to_string()),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(StdError::generic_err("No proof for legacy yet"), err);
     }
 }
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -11,4 +11,85 @@ pub enum ContractError {
 
     #[error("InvalidPrimitive")]
     InvalidPrimitive {},
+
+    #[error("EmptyBatch")]
+    EmptyBatch {},
+
+    #[error("BatchTooLarge: at most {max} ops are allowed")]
+    BatchTooLarge { max: usize },
+
+    #[error("InvalidBounds: min must not be greater than max")]
+    InvalidBounds {},
+
+    #[error("OutOfBounds: value {value} is outside the bounds of {name}")]
+    OutOfBounds { name: String, value: Uint128 },
+
+    #[error("IndexOutOfRange: index {index} is out of range for length {len}")]
+    IndexOutOfRange { index: u32, len: u32 },
+
+    #[error("EmptyVector")]
+    EmptyVector {},
+
+    #[error("DuplicateElement: {name} only accepts unique elements")]
+    DuplicateElement { name: String },
+
+    #[error("InvalidPath: {path}")]
+    InvalidPath { path: String },
+
+    #[error("ConditionFailed: unexpected value stored under {name}")]
+    ConditionFailed { name: String },
+
+    #[error("IndexNotCleared: the rank index of {path} still has entries, disable it again first")]
+    IndexNotCleared { path: String },
+
+    #[error("InvalidTag: tags must not be empty")]
+    InvalidTag {},
+
+    #[error("TooManyTags: {name} can have at most {max} tags")]
+    TooManyTags { name: String, max: usize },
+
+    #[error("InvalidTypeLock: element_type is only allowed for Vec and cannot be Vec")]
+    InvalidTypeLock {},
+
+    #[error("TypeMismatch: expected {expected}, got {got}")]
+    TypeMismatch { expected: String, got: String },
+
+    #[error("NotAllowed: value is not one of the values allowed for {name}")]
+    NotAllowed { name: String },
+
+    #[error("TooLong: {name} only accepts Strings of at most {max} bytes")]
+    TooLong { name: String, max: u32 },
+
+    #[error("TooManyChars: {name} only accepts Strings of at most {max} characters")]
+    TooManyChars { name: String, max: u32 },
+
+    #[error("TooManyItems: {name} only accepts Vecs of at most {max} elements")]
+    TooManyItems { name: String, max: u32 },
+
+    #[error("PatternMismatch: {name} only accepts Strings matching {pattern}")]
+    PatternMismatch { name: String, pattern: String },
+
+    #[error("InvalidSchema: {reason}")]
+    InvalidSchema { reason: String },
+
+    #[error("InvalidKey: {reason}")]
+    InvalidKey { reason: String },
+
+    #[error("InvalidDecimals: decimals must not exceed {max}")]
+    InvalidDecimals { max: u8 },
+
+    #[error("InvalidLimits: limits must be greater than zero")]
+    InvalidLimits {},
+
+    #[error("ImportFinished: imports are frozen")]
+    ImportFinished {},
+
+    #[error("StoreNotEmpty: the reverse index can only be changed while no values are stored")]
+    StoreNotEmpty {},
+
+    #[error("SnapshotExists: a snapshot labelled {label} already exists")]
+    SnapshotExists { label: String },
+
+    #[error("NotOldestSnapshot: {label} is not the oldest snapshot, delete the older ones first")]
+    NotOldestSnapshot { label: String },
 }
@@ -0,0 +1,510 @@
+//! Reading and writing the JSON Schema documents taken by RegisterSchema and returned by the
+//! schema queries.
+//!
+//! Messages cannot hold JSON values of mixed types, which enum lists, so schemas travel as
+//! document strings and are converted from and to KeySchema here. The supported keywords are
+//! type, enum, minimum, maximum, maxLength and items. Annotations such as title and description
+//! are accepted and dropped, any other keyword is rejected rather than silently not enforced.
+
+use cosmwasm_std::Uint128;
+
+use crate::state::{KeySchema, Primitive, SchemaType};
+
+/// Keywords that only describe a schema and have no effect on the values it allows.
+const ANNOTATIONS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "default",
+    "examples",
+];
+/// How deep arrays and objects may be nested in a document.
+const MAX_NESTING: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    /// The number as written, converted once its keyword says what it has to be.
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Reads a JSON Schema document, returning why it cannot be used if it cannot.
+pub fn parse(document: &str) -> Result<KeySchema, String> {
+    let mut parser = Parser {
+        input: document.as_bytes(),
+        pos: 0,
+    };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    if parser.pos != parser.input.len() {
+        return Err("unexpected characters after the schema".to_string());
+    }
+    read_schema(&value)
+}
+
+/// Writes schema as a JSON Schema document, with its keywords in a fixed order.
+pub fn to_string(schema: &KeySchema) -> String {
+    let mut keywords = vec![];
+    if let Some(schema_type) = schema.r#type {
+        keywords.push(format!("\"type\":\"{}\"", type_name(schema_type)));
+    }
+    if let Some(values) = &schema.r#enum {
+        let values: Vec<String> = values.iter().map(write_primitive).collect();
+        keywords.push(format!("\"enum\":[{}]", values.join(",")));
+    }
+    if let Some(min) = schema.minimum {
+        keywords.push(format!("\"minimum\":{}", min));
+    }
+    if let Some(max) = schema.maximum {
+        keywords.push(format!("\"maximum\":{}", max));
+    }
+    if let Some(max) = schema.max_length {
+        keywords.push(format!("\"maxLength\":{}", max));
+    }
+    if let Some(items) = &schema.items {
+        keywords.push(format!("\"items\":{}", to_string(items)));
+    }
+    format!("{{{}}}", keywords.join(","))
+}
+
+fn read_schema(value: &Json) -> Result<KeySchema, String> {
+    let keywords = match value {
+        Json::Object(keywords) => keywords,
+        _ => return Err("a schema must be an object".to_string()),
+    };
+    let mut schema = KeySchema::default();
+    for (i, (keyword, value)) in keywords.iter().enumerate() {
+        if keywords[..i].iter().any(|(seen, _)| seen == keyword) {
+            return Err(format!("{} is given more than once", keyword));
+        }
+        match keyword.as_str() {
+            "type" => schema.r#type = Some(read_type(value)?),
+            "enum" => match value {
+                Json::Array(values) => {
+                    schema.r#enum = Some(
+                        values
+                            .iter()
+                            .map(read_enum_value)
+                            .collect::<Result<_, _>>()?,
+                    )
+                }
+                _ => return Err("enum must be an array".to_string()),
+            },
+            "minimum" => schema.minimum = Some(Uint128::new(read_integer(keyword, value)?)),
+            "maximum" => schema.maximum = Some(Uint128::new(read_integer(keyword, value)?)),
+            "maxLength" => {
+                let max = read_integer(keyword, value)?;
+                if max > u32::MAX as u128 {
+                    return Err(format!("maxLength must not exceed {}", u32::MAX));
+                }
+                schema.max_length = Some(max as u32);
+            }
+            "items" => schema.items = Some(Box::new(read_schema(value)?)),
+            annotation if ANNOTATIONS.contains(&annotation) => {}
+            unsupported => return Err(format!("the {} keyword is not supported", unsupported)),
+        }
+    }
+    Ok(schema)
+}
+
+fn read_type(value: &Json) -> Result<SchemaType, String> {
+    match value {
+        Json::String(name) if name == "integer" => Ok(SchemaType::Integer),
+        Json::String(name) if name == "string" => Ok(SchemaType::String),
+        Json::String(name) if name == "boolean" => Ok(SchemaType::Boolean),
+        Json::String(name) if name == "array" => Ok(SchemaType::Array),
+        _ => Err("type must be one of integer, string, boolean or array".to_string()),
+    }
+}
+
+fn type_name(schema_type: SchemaType) -> &'static str {
+    match schema_type {
+        SchemaType::Integer => "integer",
+        SchemaType::String => "string",
+        SchemaType::Boolean => "boolean",
+        SchemaType::Array => "array",
+    }
+}
+
+fn read_enum_value(value: &Json) -> Result<Primitive, String> {
+    match value {
+        Json::Number(_) => Ok(Primitive::Uint128(Uint128::new(read_integer(
+            "enum", value,
+        )?))),
+        Json::String(s) => Ok(Primitive::String(s.clone())),
+        Json::Bool(b) => Ok(Primitive::Bool(*b)),
+        _ => Err("enum can only list integers, strings and booleans".to_string()),
+    }
+}
+
+fn read_integer(keyword: &str, value: &Json) -> Result<u128, String> {
+    let invalid = || format!("{} must be a non-negative integer", keyword);
+    match value {
+        Json::Number(digits) if digits.bytes().all(|b| b.is_ascii_digit()) => {
+            digits.parse().map_err(|_| invalid())
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn write_primitive(value: &Primitive) -> String {
+    match value {
+        Primitive::Uint128(number) => number.to_string(),
+        Primitive::String(s) => write_string(s),
+        Primitive::Bool(b) => b.to_string(),
+        Primitive::Vec(vector) => {
+            let elements: Vec<String> = vector.iter().map(write_primitive).collect();
+            format!("[{}]", elements.join(","))
+        }
+    }
+}
+
+fn write_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn value(&mut self, nesting: usize) -> Result<Json, String> {
+        if nesting > MAX_NESTING {
+            return Err(format!(
+                "schema is nested more than {} levels deep",
+                MAX_NESTING
+            ));
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(nesting),
+            Some(b'[') => self.array(nesting),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err("schema ends unexpectedly".to_string()),
+        }
+    }
+
+    fn object(&mut self, nesting: usize) -> Result<Json, String> {
+        self.pos += 1;
+        let mut members = vec![];
+        self.skip_whitespace();
+        if self.eat(b'}') {
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(b':') {
+                return Err(self.error("expected ':'"));
+            }
+            members.push((key, self.value(nesting + 1)?));
+            self.skip_whitespace();
+            if self.eat(b'}') {
+                return Ok(Json::Object(members));
+            }
+            if !self.eat(b',') {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self, nesting: usize) -> Result<Json, String> {
+        self.pos += 1;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.eat(b']') {
+            return Ok(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.value(nesting + 1)?);
+            self.skip_whitespace();
+            if self.eat(b']') {
+                return Ok(Json::Array(elements));
+            }
+            if !self.eat(b',') {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while let Some(b) = self.peek() {
+                if b == b'"' || b == b'\\' || b < 0x20 {
+                    break;
+                }
+                self.pos += 1;
+            }
+            // The input is a str and the run stops at ASCII bytes, so it is valid UTF-8.
+            out.push_str(std::str::from_utf8(&self.input[start..self.pos]).unwrap_or_default());
+            match self.next() {
+                Some(b'"') => return Ok(out),
+                Some(b'\\') => out.push(self.escape()?),
+                Some(_) => return Err(self.error("control characters must be escaped")),
+                None => return Err("schema ends unexpectedly".to_string()),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        match self.next() {
+            Some(b'"') => Ok('"'),
+            Some(b'\\') => Ok('\\'),
+            Some(b'/') => Ok('/'),
+            Some(b'b') => Ok('\u{8}'),
+            Some(b'f') => Ok('\u{c}'),
+            Some(b'n') => Ok('\n'),
+            Some(b'r') => Ok('\r'),
+            Some(b't') => Ok('\t'),
+            Some(b'u') => {
+                let high = self.hex4()?;
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    // A character outside the Basic Multilingual Plane, written as a surrogate pair.
+                    if !(self.eat(b'\\') && self.eat(b'u')) {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    let low = self.hex4()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                std::char::from_u32(code).ok_or_else(|| self.error("invalid escape"))
+            }
+            _ => Err(self.error("invalid escape")),
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid escape"))?;
+        self.pos += 4;
+        Ok(digits)
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        self.eat(b'-');
+        match self.next() {
+            Some(b'0') => {}
+            Some(b'1'..=b'9') => self.skip_digits(),
+            _ => return Err(self.error("invalid number")),
+        }
+        if self.eat(b'.') {
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("invalid number"));
+            }
+            self.skip_digits();
+        }
+        if self.eat(b'e') || self.eat(b'E') {
+            if !self.eat(b'+') {
+                self.eat(b'-');
+            }
+            if !matches!(self.peek(), Some(b'0'..=b'9')) {
+                return Err(self.error("invalid number"));
+            }
+            self.skip_digits();
+        }
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap_or_default();
+        Ok(Json::Number(text.to_string()))
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.input[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn skip_digits(&mut self) {
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(
+            self.peek(),
+            Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r')
+        ) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek();
+        if b.is_some() {
+            self.pos += 1;
+        }
+        b
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, reason: &str) -> String {
+        format!("{} at byte {}", reason, self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_supported_keywords() {
+        let schema = parse(
+            r#"{
+                "$schema": "https://json-schema.org/draft/2020-12/schema",
+                "title": "Codes",
+                "type": "array",
+                "items": {"type": "string", "enum": ["a\"b", "é", "😀"], "maxLength": 4}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            KeySchema {
+                r#type: Some(SchemaType::Array),
+                items: Some(Box::new(KeySchema {
+                    r#type: Some(SchemaType::String),
+                    r#enum: Some(vec![
+                        Primitive::String("a\"b".to_string()),
+                        Primitive::String("é".to_string()),
+                        Primitive::String("😀".to_string()),
+                    ]),
+                    max_length: Some(4),
+                    ..KeySchema::default()
+                })),
+                ..KeySchema::default()
+            },
+            schema
+        );
+
+        let schema = parse(
+            r#"{"enum":[1,"one",true],"minimum":0,"maximum":340282366920938463463374607431768211455}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(vec![
+                Primitive::Uint128(Uint128::new(1)),
+                Primitive::String("one".to_string()),
+                Primitive::Bool(true),
+            ]),
+            schema.r#enum
+        );
+        assert_eq!(Some(Uint128::new(0)), schema.minimum);
+        assert_eq!(Some(Uint128::new(u128::MAX)), schema.maximum);
+    }
+
+    #[test]
+    fn writes_documents_it_reads_back() {
+        let schema = KeySchema {
+            r#type: Some(SchemaType::Integer),
+            r#enum: Some(vec![
+                Primitive::Uint128(Uint128::new(5)),
+                Primitive::String("\"\n".to_string()),
+                Primitive::Bool(false),
+            ]),
+            minimum: Some(Uint128::new(1)),
+            maximum: Some(Uint128::new(10)),
+            max_length: None,
+            items: None,
+        };
+        let document = to_string(&schema);
+        assert_eq!(
+            r#"{"type":"integer","enum":[5,"\"\n",false],"minimum":1,"maximum":10}"#,
+            document
+        );
+        assert_eq!(Ok(schema), parse(&document));
+        assert_eq!("{}", to_string(&KeySchema::default()));
+    }
+
+    #[test]
+    fn rejects_what_it_cannot_enforce() {
+        let reason = |document: &str| parse(document).unwrap_err();
+        assert_eq!("a schema must be an object", reason("true"));
+        assert_eq!(
+            "the pattern keyword is not supported",
+            reason(r#"{"pattern":"a*"}"#)
+        );
+        assert_eq!(
+            "type must be one of integer, string, boolean or array",
+            reason(r#"{"type":"number"}"#)
+        );
+        assert_eq!(
+            "minimum must be a non-negative integer",
+            reason(r#"{"minimum":"1"}"#)
+        );
+        assert_eq!(
+            "minimum must be a non-negative integer",
+            reason(r#"{"minimum":-1}"#)
+        );
+        assert_eq!(
+            "maximum must be a non-negative integer",
+            reason(r#"{"maximum":1.5}"#)
+        );
+        assert_eq!(
+            "enum can only list integers, strings and booleans",
+            reason(r#"{"enum":[null]}"#)
+        );
+        assert_eq!(
+            "type is given more than once",
+            reason(r#"{"type":"string","type":"integer"}"#)
+        );
+        assert_eq!("unexpected characters after the schema", reason("{} {}"));
+        assert_eq!("expected ',' or '}' at byte 12", reason(r#"{"type":"a" "#));
+        assert_eq!("schema ends unexpectedly", reason(r#"{"title":"#));
+        assert!(reason(&"[".repeat(100)).contains("nested"));
+    }
+}
@@ -1,5 +1,7 @@
 pub mod contract;
 mod error;
+pub mod json_schema;
+pub mod merkle;
 pub mod msg;
 pub mod state;
 
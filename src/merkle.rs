@@ -0,0 +1,579 @@
+//! A sparse Merkle tree over every stored value, committing to the whole store with a single
+//! root hash.
+//!
+//! Keys are placed by the bits of sha256(key), most significant bit first. A subtree holding a
+//! single value is stored as that leaf alone, so leaves sit at the shallowest depth at which
+//! their key is unique and the root only depends on the stored values, not on the order they
+//! were written in. The hashes are
+//!
+//! - leaf: `sha256(0x00 || sha256(key) || value_hash)`
+//! - internal node: `sha256(0x01 || left || right)`
+//! - empty subtree, and the root of an empty store: 32 zero bytes
+//!
+//! The value hash of a single value is the sha256 of its JSON encoding. A Vec is committed to
+//! through a binary tree over its elements instead, so that changing one element of a chunked
+//! Vec only rehashes the path above it: its value hash is `sha256(0x02 || len || root)` with
+//! len as a big endian u32. The leaves of the element tree are the value hashes of the
+//! elements, the tree is as shallow as possible for len of them, subtrees past the last
+//! element are empty and internal nodes are hashed like those of the main tree. The element
+//! trees of chunked Vecs are kept in storage, those of inline Vecs are computed when needed.
+//!
+//! A proof for a value lists the hashes of the siblings on the path from the root down to its
+//! leaf, and is checked with verify.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+
+use cosmwasm_std::{to_vec, Binary, StdResult, Storage};
+use cw_storage_plus::Map;
+
+use crate::state::Primitive;
+
+pub type Hash = [u8; 32];
+
+/// Hash of an empty subtree.
+pub const EMPTY_HASH: Hash = [0; 32];
+
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+const VEC_PREFIX: u8 = 2;
+/// Depth of the deepest possible leaf, one per bit of the key hash.
+const MAX_DEPTH: usize = 256;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Node {
+    Leaf { key: Hash, value: Hash },
+    Internal { hash: Hash },
+}
+
+impl Node {
+    fn hash(&self) -> Hash {
+        match self {
+            Node::Leaf { key, value } => leaf_hash(key, value),
+            Node::Internal { hash } => *hash,
+        }
+    }
+}
+
+/// Nodes by their position, see position. Empty subtrees are not stored.
+const NODES: Map<&[u8], Node> = Map::new("merkle_nodes");
+/// Element tree nodes of chunked Vecs by Vec name and element_position. Empty subtrees are not
+/// stored.
+const ELEMENT_NODES: Map<(&str, &[u8]), Hash> = Map::new("merkle_elements");
+
+pub fn key_hash(name: &str) -> Hash {
+    Sha256::digest(name.as_bytes()).into()
+}
+
+pub fn value_hash(value: &Primitive) -> StdResult<Hash> {
+    match value {
+        Primitive::Vec(vector) => vec_hash(vector),
+        _ => Ok(Sha256::digest(&to_vec(value)?).into()),
+    }
+}
+
+/// The value hash of a Vec holding elements.
+pub fn vec_hash(elements: &[Primitive]) -> StdResult<Hash> {
+    let mut level = elements
+        .iter()
+        .map(value_hash)
+        .collect::<StdResult<Vec<Hash>>>()?;
+    while level.len() > 1 {
+        level = parent_level(&level);
+    }
+    let root = level.first().copied().unwrap_or(EMPTY_HASH);
+    Ok(commit_vec(elements.len() as u32, &root))
+}
+
+/// The value hash of the chunked Vec of len elements stored under name.
+pub fn stored_vec_hash(storage: &dyn Storage, name: &str, len: u32) -> StdResult<Hash> {
+    let root = match len {
+        0 => EMPTY_HASH,
+        _ => element_node(storage, name, vec_depth(len), 0)?,
+    };
+    Ok(commit_vec(len, &root))
+}
+
+fn commit_vec(len: u32, root: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([VEC_PREFIX]);
+    hasher.update(len.to_be_bytes());
+    hasher.update(root);
+    hasher.finalize().into()
+}
+
+pub fn leaf_hash(key: &Hash, value: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(key);
+    hasher.update(value);
+    hasher.finalize().into()
+}
+
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Whether the path to key turns right below depth.
+pub fn bit(key: &Hash, depth: usize) -> bool {
+    key[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+/// The root hash of the tree, EMPTY_HASH for an empty store.
+pub fn root(storage: &dyn Storage) -> StdResult<Hash> {
+    Ok(NODES
+        .may_load(storage, &position(&EMPTY_HASH, 0))?
+        .map_or(EMPTY_HASH, |node| node.hash()))
+}
+
+/// Stores value as the leaf of name, replacing the previous one.
+pub fn insert(storage: &mut dyn Storage, name: &str, value: &Primitive) -> StdResult<()> {
+    insert_hash(storage, name, value_hash(value)?)
+}
+
+/// Like insert, for a value of which only the value hash is at hand.
+pub fn insert_hash(storage: &mut dyn Storage, name: &str, value: Hash) -> StdResult<()> {
+    let key = key_hash(name);
+    let mut depth = 0;
+    loop {
+        let here = position(&key, depth);
+        match NODES.may_load(storage, &here)? {
+            Some(Node::Internal { .. }) => depth += 1,
+            Some(Node::Leaf {
+                key: other,
+                value: other_value,
+            }) if other != key => {
+                // Both leaves move below the first bit their keys differ in, with internal nodes
+                // on the way down to it.
+                let split = first_difference(&key, &other);
+                NODES.remove(storage, &here);
+                let other_leaf = Node::Leaf {
+                    key: other,
+                    value: other_value,
+                };
+                NODES.save(storage, &position(&other, split + 1), &other_leaf)?;
+                NODES.save(
+                    storage,
+                    &position(&key, split + 1),
+                    &Node::Leaf { key, value },
+                )?;
+                return update_path(storage, &key, split + 1);
+            }
+            _ => {
+                NODES.save(storage, &here, &Node::Leaf { key, value })?;
+                return update_path(storage, &key, depth);
+            }
+        }
+    }
+}
+
+/// Removes the leaf of name, if there is one.
+pub fn remove(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    let key = key_hash(name);
+    let mut depth = 0;
+    loop {
+        match NODES.may_load(storage, &position(&key, depth))? {
+            Some(Node::Internal { .. }) => depth += 1,
+            Some(Node::Leaf { key: other, .. }) if other == key => break,
+            _ => return Ok(()),
+        }
+    }
+    NODES.remove(storage, &position(&key, depth));
+
+    // A leaf left alone below an internal node takes its place, up to the first node that still
+    // has two non-empty subtrees.
+    let mut lone: Option<Node> = None;
+    while depth > 0 {
+        let parent = depth - 1;
+        let sibling = child(&key, parent, !bit(&key, parent));
+        match (&lone, NODES.may_load(storage, &sibling)?) {
+            (None, Some(leaf @ Node::Leaf { .. })) => {
+                NODES.remove(storage, &sibling);
+                lone = Some(leaf);
+            }
+            (_, None) => {}
+            _ => break,
+        }
+        NODES.remove(storage, &position(&key, depth));
+        match &lone {
+            Some(leaf) => NODES.save(storage, &position(&key, parent), leaf)?,
+            None => NODES.remove(storage, &position(&key, parent)),
+        }
+        depth = parent;
+    }
+    update_path(storage, &key, depth)
+}
+
+/// The hashes of the siblings on the path to the leaf of name, from the root down, or None if
+/// there is no leaf for name.
+pub fn prove(storage: &dyn Storage, name: &str) -> StdResult<Option<Vec<Hash>>> {
+    let key = key_hash(name);
+    let mut siblings = vec![];
+    for depth in 0..MAX_DEPTH {
+        match NODES.may_load(storage, &position(&key, depth))? {
+            Some(Node::Internal { .. }) => {
+                let sibling = child(&key, depth, !bit(&key, depth));
+                siblings.push(subtree_hash(storage, &sibling)?);
+            }
+            Some(Node::Leaf { key: other, .. }) if other == key => return Ok(Some(siblings)),
+            _ => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+/// Checks that root commits to value being stored under name, given the proof returned with it
+/// by GetValueWithProof. Does not need access to the contract.
+pub fn verify(root: &[u8], name: &str, value: &Primitive, proof: &[Binary]) -> StdResult<bool> {
+    if proof.len() >= MAX_DEPTH {
+        return Ok(false);
+    }
+    let key = key_hash(name);
+    let mut hash = leaf_hash(&key, &value_hash(value)?);
+    for (depth, sibling) in proof.iter().enumerate().rev() {
+        let sibling: Hash = match sibling.as_slice().try_into() {
+            Ok(sibling) => sibling,
+            Err(_) => return Ok(false),
+        };
+        hash = match bit(&key, depth) {
+            true => node_hash(&sibling, &hash),
+            false => node_hash(&hash, &sibling),
+        };
+    }
+    Ok(hash[..] == *root)
+}
+
+/// Recomputes the internal nodes on the path to key above depth.
+fn update_path(storage: &mut dyn Storage, key: &Hash, depth: usize) -> StdResult<()> {
+    for depth in (0..depth).rev() {
+        let left = subtree_hash(storage, &child(key, depth, false))?;
+        let right = subtree_hash(storage, &child(key, depth, true))?;
+        let node = Node::Internal {
+            hash: node_hash(&left, &right),
+        };
+        NODES.save(storage, &position(key, depth), &node)?;
+    }
+    Ok(())
+}
+
+fn subtree_hash(storage: &dyn Storage, position: &[u8]) -> StdResult<Hash> {
+    Ok(NODES
+        .may_load(storage, position)?
+        .map_or(EMPTY_HASH, |node| node.hash()))
+}
+
+/// The depth followed by the first depth bits of key, which identifies a subtree.
+fn position(key: &Hash, depth: usize) -> Vec<u8> {
+    let mut position = (depth as u16).to_be_bytes().to_vec();
+    let bytes = (depth + 7) / 8;
+    position.extend_from_slice(&key[..bytes]);
+    let partial = depth % 8;
+    if partial > 0 {
+        position[1 + bytes] &= 0xff << (8 - partial);
+    }
+    position
+}
+
+/// Position of the left or right child of the node at depth on the path to key.
+fn child(key: &Hash, depth: usize, right: bool) -> Vec<u8> {
+    let mut key = *key;
+    let mask = 0x80 >> (depth % 8);
+    match right {
+        true => key[depth / 8] |= mask,
+        false => key[depth / 8] &= !mask,
+    }
+    position(&key, depth + 1)
+}
+
+fn first_difference(a: &Hash, b: &Hash) -> usize {
+    (0..MAX_DEPTH)
+        .find(|&depth| bit(a, depth) != bit(b, depth))
+        .unwrap_or(MAX_DEPTH)
+}
+
+/// Records element as the one at index of the chunked Vec name, which holds len elements
+/// afterwards. Covers both replacing an element and appending one.
+pub fn set_element(
+    storage: &mut dyn Storage,
+    name: &str,
+    index: u32,
+    element: &Primitive,
+    len: u32,
+) -> StdResult<()> {
+    let leaf = element_position(0, index as u64);
+    ELEMENT_NODES.save(storage, (name, &leaf), &value_hash(element)?)?;
+    update_element_path(storage, name, index, len, vec_depth(len))
+}
+
+/// Drops the last element of the chunked Vec name, which holds len elements afterwards.
+pub fn pop_element(storage: &mut dyn Storage, name: &str, len: u32) -> StdResult<()> {
+    let depth = vec_depth(len + 1);
+    update_element_path(storage, name, len, len, depth)?;
+    // The tree may get shallower, leaving roots of the old depth above the new one.
+    for level in vec_depth(len) + 1..=depth {
+        ELEMENT_NODES.remove(storage, (name, &element_position(level, 0)));
+    }
+    Ok(())
+}
+
+/// Replaces the element tree of the chunked Vec name, which held old_len elements, with one over
+/// elements.
+pub fn set_elements(
+    storage: &mut dyn Storage,
+    name: &str,
+    old_len: u32,
+    elements: &[Primitive],
+) -> StdResult<()> {
+    clear_elements(storage, name, old_len)?;
+    let mut level = elements
+        .iter()
+        .map(value_hash)
+        .collect::<StdResult<Vec<Hash>>>()?;
+    let mut depth = 0;
+    while !level.is_empty() {
+        for (index, hash) in level.iter().enumerate() {
+            let position = element_position(depth, index as u64);
+            ELEMENT_NODES.save(storage, (name, &position), hash)?;
+        }
+        if level.len() == 1 {
+            break;
+        }
+        level = parent_level(&level);
+        depth += 1;
+    }
+    Ok(())
+}
+
+/// Removes the element tree of the chunked Vec of len elements stored under name.
+pub fn clear_elements(storage: &mut dyn Storage, name: &str, len: u32) -> StdResult<()> {
+    for level in 0..=vec_depth(len) {
+        let count = (len as u64 + (1 << level) - 1) >> level;
+        for index in 0..count {
+            ELEMENT_NODES.remove(storage, (name, &element_position(level, index)));
+        }
+    }
+    Ok(())
+}
+
+/// Recomputes the nodes above the element at index, up to depth, for a Vec of len elements.
+/// Nodes starting past the last element are removed.
+fn update_element_path(
+    storage: &mut dyn Storage,
+    name: &str,
+    index: u32,
+    len: u32,
+    depth: u8,
+) -> StdResult<()> {
+    for level in 0..=depth {
+        let node = index as u64 >> level;
+        let position = element_position(level, node);
+        if node << level >= len as u64 {
+            ELEMENT_NODES.remove(storage, (name, &position));
+        } else if level > 0 {
+            let left = element_node(storage, name, level - 1, node * 2)?;
+            let right = element_node(storage, name, level - 1, node * 2 + 1)?;
+            ELEMENT_NODES.save(storage, (name, &position), &node_hash(&left, &right))?;
+        }
+    }
+    Ok(())
+}
+
+fn element_node(storage: &dyn Storage, name: &str, level: u8, index: u64) -> StdResult<Hash> {
+    Ok(ELEMENT_NODES
+        .may_load(storage, (name, &element_position(level, index)))?
+        .unwrap_or(EMPTY_HASH))
+}
+
+/// The level above the elements followed by the index of the node within it.
+fn element_position(level: u8, index: u64) -> Vec<u8> {
+    let mut position = vec![level];
+    position.extend_from_slice(&(index as u32).to_be_bytes());
+    position
+}
+
+/// Depth of the element tree of a Vec with len elements.
+fn vec_depth(len: u32) -> u8 {
+    match len {
+        0 | 1 => 0,
+        _ => (32 - (len - 1).leading_zeros()) as u8,
+    }
+}
+
+fn parent_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| node_hash(&pair[0], pair.get(1).unwrap_or(&EMPTY_HASH)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::{Order, Uint128};
+
+    fn number(value: u128) -> Primitive {
+        Primitive::Uint128(Uint128::new(value))
+    }
+
+    #[test]
+    fn position_masks_unused_bits() {
+        let key = [0xff; 32];
+        assert_eq!(vec![0, 0], position(&key, 0));
+        assert_eq!(vec![0, 3, 0xe0], position(&key, 3));
+        assert_eq!(vec![0, 8, 0xff], position(&key, 8));
+        assert_eq!(vec![0, 3, 0xa0], child(&[0x80; 32], 2, true));
+        assert_eq!(vec![0, 3, 0x80], child(&[0xa0; 32], 2, false));
+    }
+
+    #[test]
+    fn single_value() {
+        let mut storage = MockStorage::new();
+        assert_eq!(EMPTY_HASH, root(&storage).unwrap());
+
+        insert(&mut storage, "a", &number(1)).unwrap();
+        let expected = leaf_hash(&key_hash("a"), &value_hash(&number(1)).unwrap());
+        assert_eq!(expected, root(&storage).unwrap());
+
+        remove(&mut storage, "a").unwrap();
+        assert_eq!(EMPTY_HASH, root(&storage).unwrap());
+        // Removing a missing key changes nothing.
+        remove(&mut storage, "a").unwrap();
+        assert_eq!(EMPTY_HASH, root(&storage).unwrap());
+    }
+
+    #[test]
+    fn root_does_not_depend_on_history() {
+        let names: Vec<String> = (0..40).map(|i| format!("key{}", i)).collect();
+
+        let mut forward = MockStorage::new();
+        for (i, name) in names.iter().enumerate() {
+            insert(&mut forward, name, &number(i as u128)).unwrap();
+        }
+        let mut backward = MockStorage::new();
+        for (i, name) in names.iter().enumerate().rev() {
+            insert(&mut backward, name, &number(100)).unwrap();
+            insert(&mut backward, name, &number(i as u128)).unwrap();
+        }
+        insert(&mut backward, "extra", &number(1)).unwrap();
+        assert_ne!(root(&forward).unwrap(), root(&backward).unwrap());
+        remove(&mut backward, "extra").unwrap();
+        assert_eq!(root(&forward).unwrap(), root(&backward).unwrap());
+
+        // Removing every other key leaves the tree a fresh insert of the rest would build.
+        let mut rest = MockStorage::new();
+        for (i, name) in names.iter().enumerate() {
+            if i % 2 == 0 {
+                remove(&mut forward, name).unwrap();
+            } else {
+                insert(&mut rest, name, &number(i as u128)).unwrap();
+            }
+        }
+        assert_eq!(root(&rest).unwrap(), root(&forward).unwrap());
+        assert_eq!(
+            NODES.range(&rest, None, None, Order::Ascending).count(),
+            NODES.range(&forward, None, None, Order::Ascending).count()
+        );
+
+        for (i, name) in names.iter().enumerate() {
+            if i % 2 == 1 {
+                remove(&mut forward, name).unwrap();
+            }
+        }
+        assert_eq!(EMPTY_HASH, root(&forward).unwrap());
+        assert_eq!(
+            0,
+            NODES.range(&forward, None, None, Order::Ascending).count()
+        );
+    }
+
+    #[test]
+    fn proofs() {
+        let mut storage = MockStorage::new();
+        for i in 0..20 {
+            insert(&mut storage, &format!("key{}", i), &number(i)).unwrap();
+        }
+        let expected = root(&storage).unwrap();
+        let to_binary =
+            |proof: Vec<Hash>| -> Vec<Binary> { proof.into_iter().map(Binary::from).collect() };
+
+        for i in 0..20 {
+            let name = format!("key{}", i);
+            let proof = to_binary(prove(&storage, &name).unwrap().unwrap());
+            assert!(!proof.is_empty());
+            assert!(verify(&expected, &name, &number(i), &proof).unwrap());
+            assert!(!verify(&expected, &name, &number(i + 1), &proof).unwrap());
+            assert!(!verify(&expected, "other", &number(i), &proof).unwrap());
+            assert!(!verify(&expected, &name, &number(i), &proof[1..]).unwrap());
+        }
+        assert_eq!(None, prove(&storage, "missing").unwrap());
+
+        let mut single = MockStorage::new();
+        insert(&mut single, "a", &number(1)).unwrap();
+        let proof = prove(&single, "a").unwrap().unwrap();
+        assert!(proof.is_empty());
+        assert!(verify(&root(&single).unwrap(), "a", &number(1), &[]).unwrap());
+        assert!(!verify(
+            &root(&single).unwrap(),
+            "a",
+            &number(1),
+            &[Binary::from(EMPTY_HASH)]
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn element_trees_follow_changes() {
+        let mut storage = MockStorage::new();
+        let stored_nodes = |storage: &MockStorage| {
+            ELEMENT_NODES
+                .range(storage, None, None, Order::Ascending)
+                .count()
+        };
+        let mut elements = vec![];
+        for i in 0..40 {
+            elements.push(number(i));
+            let len = elements.len() as u32;
+            set_element(&mut storage, "vec", i as u32, &elements[i as usize], len).unwrap();
+            assert_eq!(
+                vec_hash(&elements).unwrap(),
+                stored_vec_hash(&storage, "vec", len).unwrap()
+            );
+        }
+        for i in (0..40).step_by(7) {
+            elements[i] = number(100);
+            set_element(&mut storage, "vec", i as u32, &elements[i], 40).unwrap();
+        }
+        assert_eq!(
+            vec_hash(&elements).unwrap(),
+            stored_vec_hash(&storage, "vec", 40).unwrap()
+        );
+
+        // Building the tree in one go gives the same nodes.
+        let mut built = MockStorage::new();
+        set_elements(&mut built, "vec", 0, &elements).unwrap();
+        assert_eq!(stored_nodes(&storage), stored_nodes(&built));
+        assert_eq!(
+            stored_vec_hash(&storage, "vec", 40).unwrap(),
+            stored_vec_hash(&built, "vec", 40).unwrap()
+        );
+
+        while elements.pop().is_some() {
+            let len = elements.len() as u32;
+            pop_element(&mut storage, "vec", len).unwrap();
+            assert_eq!(
+                vec_hash(&elements).unwrap(),
+                stored_vec_hash(&storage, "vec", len).unwrap()
+            );
+        }
+        assert_eq!(0, stored_nodes(&storage));
+        clear_elements(&mut built, "vec", 40).unwrap();
+        assert_eq!(0, stored_nodes(&built));
+    }
+}
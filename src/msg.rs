@@ -1,10 +1,36 @@
-use crate::state::Primitive;
-use cosmwasm_std::Addr;
+use crate::state::{
+    Bounds, Change, Constraint, KeyInfo, KeyRules, Limits, Primitive, PrimitiveType, Snapshot,
+    TypeLock,
+};
+use cosmwasm_std::{Addr, Binary, Order, Uint128};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct InstantiateMsg {
+    /// Maintain an index from scalar values to the keys holding them, which FindKeysByValue
+    /// requires. Disabled by default as it adds to the cost of every write.
+    pub reverse_index: Option<bool>,
+    /// Defaults to the sender.
+    pub owner: Option<String>,
+    /// Key used by messages that do not specify a name, `default` if not set.
+    pub default_key: Option<String>,
+    pub key_rules: Option<KeyRules>,
+    /// Every limit has to be greater than zero.
+    pub limits: Option<Limits>,
+    /// Values stored at instantiation, validated like SetValue. Instantiation fails if any of
+    /// them is rejected.
+    pub values: Option<Vec<InitialValue>>,
+    /// See Config::change_retention.
+    pub change_retention: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct InstantiateMsg {}
+pub struct InitialValue {
+    /// If name is not specified the default key will be used.
+    pub name: Option<String>,
+    pub value: Primitive,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
@@ -15,26 +41,721 @@ pub enum ExecuteMsg {
         value: Primitive,
     },
     /// If name is not specified the default key will be used.
-    DeleteValue { name: Option<String> },
+    DeleteValue {
+        name: Option<String>,
+    },
+    /// Applies every op in order within a single execution. If any op fails the whole batch
+    /// is rejected and none of the changes are stored.
+    Batch {
+        ops: Vec<Op>,
+    },
+    /// Adds one to the Uint128 stored under name. A missing key is treated as zero.
+    /// If name is not specified the default key will be used.
+    Increment {
+        name: Option<String>,
+    },
+    /// Subtracts one from the Uint128 stored under name. A missing key is treated as zero.
+    /// If name is not specified the default key will be used.
+    Decrement {
+        name: Option<String>,
+    },
+    /// Adds amount to the Uint128 stored under name. A missing key is treated as zero.
+    /// If name is not specified the default key will be used.
+    Add {
+        name: Option<String>,
+        amount: Uint128,
+    },
+    /// Subtracts amount from the Uint128 stored under name. A missing key is treated as zero.
+    /// If name is not specified the default key will be used.
+    Sub {
+        name: Option<String>,
+        amount: Uint128,
+    },
+    /// Appends value to the Vec stored under name. A missing key is treated as an empty Vec.
+    /// If name is not specified the default key will be used.
+    VecPush {
+        name: Option<String>,
+        value: Primitive,
+    },
+    /// Removes the last element of the Vec stored under name.
+    /// If name is not specified the default key will be used.
+    VecPop {
+        name: Option<String>,
+    },
+    /// Inserts value at index, shifting all elements after it to the right.
+    /// If name is not specified the default key will be used.
+    VecInsert {
+        name: Option<String>,
+        index: u32,
+        value: Primitive,
+    },
+    /// Removes the element at index, shifting all elements after it to the left.
+    /// If name is not specified the default key will be used.
+    VecRemove {
+        name: Option<String>,
+        index: u32,
+    },
+    /// Replaces the element at index.
+    /// If name is not specified the default key will be used.
+    VecSet {
+        name: Option<String>,
+        index: u32,
+        value: Primitive,
+    },
+    /// Appends value to the Vec stored under name unless it is already present. A missing key
+    /// is treated as an empty Vec.
+    /// If name is not specified the default key will be used.
+    SetAdd {
+        name: Option<String>,
+        value: Primitive,
+    },
+    /// Removes value from the Vec stored under name if it is present.
+    /// If name is not specified the default key will be used.
+    SetRemove {
+        name: Option<String>,
+        value: Primitive,
+    },
+    /// When unique is true every write to name has to keep its Vec free of duplicates. The
+    /// flag is removed with the key.
+    /// If name is not specified the default key will be used.
+    SetUnique {
+        name: Option<String>,
+        unique: bool,
+    },
+    /// Deletes keys below path, for example `oracle/btc/price` for the path `oracle`. The key
+    /// named path itself is kept. At most MAX_PREFIX_DELETIONS keys are deleted per call and the
+    /// done attribute of the response is false while keys remain.
+    DeletePrefix {
+        path: String,
+    },
+    /// Indexes values stored before an index or the state root was introduced. At most limit
+    /// keys after start_after are scanned, and the response has a next_start_after attribute
+    /// while there may be more keys left.
+    RebuildIndexes {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Moves Vecs above the chunking threshold that are still stored as a single blob to the
+    /// chunked layout. At most limit keys after start_after are scanned, and the response has a
+    /// next_start_after attribute while there may be more keys left.
+    ChunkVectors {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Restricts the Uint128 values that can be stored under name. Both limits are inclusive
+    /// and passing None for both removes the bounds. Bounds are removed with the key.
+    /// If name is not specified the default key will be used.
+    SetBounds {
+        name: Option<String>,
+        min: Option<Uint128>,
+        max: Option<Uint128>,
+    },
+    /// Enables or disables the rank index over the Uint128 values of every key below path, which
+    /// TopN and RankOf require. Values stored before enabling are indexed by RebuildIndexes, and
+    /// both queries fail for path until it has scanned every key below it.
+    /// Disabling removes at most MAX_PREFIX_DELETIONS entries per call and the done attribute of
+    /// the response is false while entries remain.
+    SetRanked {
+        path: String,
+        ranked: bool,
+    },
+    /// Describes the key name for human readers, which has to hold a value. Passing None for
+    /// every field removes the metadata, which is also removed with the key. decimals must not
+    /// exceed MAX_DECIMALS.
+    /// If name is not specified the default key will be used.
+    SetKeyInfo {
+        name: Option<String>,
+        description: Option<String>,
+        unit: Option<String>,
+        decimals: Option<u8>,
+    },
+    /// Restricts the type of the values stored under scope, passing None removes the lock. A key
+    /// has to satisfy its own lock and the locks of every prefix it is below. Locking a key
+    /// checks its current value, locking a prefix only applies to later writes.
+    SetTypeLock {
+        scope: KeyScope,
+        lock: Option<TypeLock>,
+    },
+    /// Replaces the constraints of scope, passing none removes them. A key has to satisfy its own
+    /// constraints and those of every prefix it is below. Constraining a key checks its current
+    /// value, constraining a prefix only applies to later writes.
+    SetConstraints {
+        scope: KeyScope,
+        constraints: Vec<Constraint>,
+    },
+    /// Publishes the expected shape of name_or_prefix and enforces it on writes, replacing any
+    /// schema registered for it before. A name ending with `/` applies to every key starting
+    /// with it. Registering for a key checks its current value, registering for a prefix only
+    /// applies to later writes. schema is a JSON Schema document using the type, enum,
+    /// minimum, maximum, maxLength and items keywords, annotations such as title are dropped.
+    RegisterSchema {
+        name_or_prefix: String,
+        schema: String,
+    },
+    RemoveSchema {
+        name_or_prefix: String,
+    },
+    /// Replaces the rules key names written from now on have to follow. The default key has to
+    /// follow them as well.
+    SetKeyRules {
+        rules: KeyRules,
+    },
+    /// Applies the default key, key rules, limits, change retention and reverse index of the
+    /// config returned by ExportState, keeping the owner. The reverse index can only be changed
+    /// while no values are stored. Rejected once FinishImport was executed.
+    ImportConfig {
+        config: ConfigResponse,
+    },
+    /// Stores metadata returned by ExportMetadata, checked like the messages setting it. Import
+    /// it before the values so that they are checked against it. At most Limits::max_batch_ops
+    /// items are accepted per call. Rejected once FinishImport was executed.
+    ImportMetadata {
+        metadata: Vec<Metadata>,
+    },
+    /// Stores entries returned by ExportState, including their metadata, to clone another
+    /// instance. Entries replace existing keys and are checked like the messages setting their
+    /// parts, except that the key rules are not applied to their names. At most
+    /// Limits::max_batch_ops entries are accepted per call. Rejected once FinishImport was
+    /// executed.
+    ImportState {
+        entries: Vec<ExportEntry>,
+    },
+    /// Freezes imports for good, so that the cloned state cannot be changed through the import
+    /// messages.
+    FinishImport {},
+    /// Adds tags to the key name, which has to hold a value. Tags are removed with the key.
+    /// If name is not specified the default key will be used.
+    SetTags {
+        name: Option<String>,
+        tags: Vec<String>,
+    },
+    /// Removes tags from the key name. Tags it does not have are ignored.
+    /// If name is not specified the default key will be used.
+    RemoveTags {
+        name: Option<String>,
+        tags: Vec<String>,
+    },
+    /// Takes a snapshot of every value under a new label. Taking it is cheap, afterwards the
+    /// first change of each key stores the value it replaces. For a chunked Vec that is the
+    /// whole Vec, so the first element write to one after each snapshot costs as much as
+    /// rewriting it.
+    CreateSnapshot {
+        label: String,
+    },
+    /// Deletes the oldest snapshot, which label has to name, together with the changes recorded
+    /// for it. Restoring a snapshot also undoes the changes recorded for later ones, so
+    /// snapshots are deleted oldest first. At most limit changes are removed per call and the
+    /// done attribute is false while more remain, a label of None only continues removing them.
+    DeleteSnapshot {
+        label: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Writes back the values the store held when the snapshot was taken, including removing
+    /// the keys created since. At most limit changes after start_after are undone per call and
+    /// the response data has next_start_after set while there may be more, so a restore
+    /// is only complete once it is unset. Values still have to satisfy the current rules, and
+    /// only values are restored, not their metadata. Other writes should wait for the restore
+    /// to complete.
+    RestoreSnapshot {
+        label: String,
+        start_after: Option<SnapshotCursor>,
+        limit: Option<u32>,
+    },
+    /// Sets Config::change_retention, None keeps change log entries for good.
+    SetChangeRetention {
+        blocks: Option<u64>,
+    },
+    /// Removes at most limit of the oldest change log entries that are older than the retention
+    /// window. The done attribute is false while there may be more to remove.
+    PruneChanges {
+        limit: Option<u32>,
+    },
+}
+
+/// Selects what a rule applies to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyScope {
+    /// A single key. If name is not specified the default key will be used.
+    Key { name: Option<String> },
+    /// Every key below path, for example `oracle/btc/price` for the path `oracle`. The empty
+    /// path covers every key.
+    Prefix { path: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Op {
+    /// If name is not specified the default key will be used.
+    Set {
+        name: Option<String>,
+        value: Primitive,
+    },
+    /// If name is not specified the default key will be used.
+    Delete { name: Option<String> },
+    /// Only sets the value if the currently stored value equals `expected`. An `expected` of
+    /// None requires the key to be unset.
+    SetIf {
+        name: Option<String>,
+        expected: Option<Primitive>,
+        value: Primitive,
+    },
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Config {},
+    /// The metadata set by SetKeyInfo is included when with_info is true.
     /// If name is not specified the default key will be used.
     GetValue {
         name: Option<String>,
+        with_info: Option<bool>,
+    },
+    /// If name is not specified the default key will be used.
+    KeyInfo {
+        name: Option<String>,
+    },
+    /// Checks whether the Vec stored under name contains value. A missing key is treated as
+    /// an empty Vec.
+    /// If name is not specified the default key will be used.
+    VecContains {
+        name: Option<String>,
+        value: Primitive,
+    },
+    /// Returns at most limit elements of the Vec stored under name, starting at offset. A
+    /// missing key is treated as an empty Vec.
+    /// If name is not specified the default key will be used.
+    GetVecSlice {
+        name: Option<String>,
+        offset: Option<u32>,
+        limit: Option<u32>,
+    },
+    /// A missing key is treated as an empty Vec.
+    /// If name is not specified the default key will be used.
+    GetVecLength {
+        name: Option<String>,
+    },
+    /// Lists the immediate children of path in key order. Keys are returned by their last
+    /// segment, and sub-directories by their segment followed by a `/`. Either form can be
+    /// passed back as start_after. If path is not specified the root is listed.
+    ListChildren {
+        path: Option<String>,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the values of every key starting with prefix, in ascending key order.
+    ListByPrefix {
+        prefix: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the values of the keys from start (inclusive) to end (exclusive). Descending order
+    /// walks from end towards start.
+    ListRange {
+        start: Option<String>,
+        end: Option<String>,
+        order: Option<OrderBy>,
+        limit: Option<u32>,
+    },
+    /// Lists the values of every key currently holding the given type, in ascending key order.
+    ListByType {
+        r#type: PrimitiveType,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the keys currently holding the given scalar value, in ascending key order. Only
+    /// available when the reverse index was enabled at instantiation.
+    FindKeysByValue {
+        value: Primitive,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// If name is not specified the default key will be used.
+    GetBounds {
+        name: Option<String>,
+    },
+    /// Lists the highest Uint128 values below a ranked path, or the lowest in ascending order.
+    /// Keys holding the same value are ordered by key. Defaults to descending order.
+    TopN {
+        prefix: String,
+        limit: Option<u32>,
+        order: Option<OrderBy>,
+    },
+    /// Returns the type lock set on scope itself, not including those of enclosing prefixes.
+    GetTypeLock {
+        scope: KeyScope,
+    },
+    /// Returns the constraints set on scope itself, not including those of enclosing prefixes.
+    GetConstraints {
+        scope: KeyScope,
+    },
+    /// Returns the schema registered for name_or_prefix itself, not including those of enclosing
+    /// prefixes.
+    GetSchema {
+        name_or_prefix: String,
+    },
+    /// Lists the registered schemas in ascending order of name_or_prefix.
+    ListSchemas {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists every value with its metadata in ascending key order, together with the config, to
+    /// be passed to ImportState of another instance. The response has next_start_after set
+    /// while there may be more keys. Type locks, constraints, schemas and ranked prefixes are
+    /// listed by ExportMetadata instead.
+    ExportState {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists the type locks, constraints, schemas and ranked prefixes, in that order and each in
+    /// ascending key order, to be passed to ImportMetadata of another instance. The response
+    /// has next_start_after set while there may be more.
+    ExportMetadata {
+        start_after: Option<MetadataCursor>,
+        limit: Option<u32>,
+    },
+    /// Lists the keys with the given tag, in ascending key order.
+    ListByTag {
+        tag: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Aggregates the Uint128 values of the keys starting with prefix, ignoring other types. At
+    /// most limit of them after start_after are read per query, and next_start_after is set in
+    /// the response while there may be more. The values of all pages combine into the total.
+    Aggregate {
+        prefix: String,
+        op: AggregateOp,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns the position of the value stored under name among the values below its closest
+    /// ranked path, where 1 is the highest. Keys holding the same value share a rank. The
+    /// entries above the value are counted, so values ranked below 10000 are rejected.
+    /// If name is not specified the default key will be used.
+    RankOf {
+        name: Option<String>,
+    },
+    /// Lists the snapshots in the order they were taken.
+    ListSnapshots {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+    /// Lists the keys whose value differs from the one they held when the snapshot was taken.
+    /// At most limit changes after start_after are read per query, so a page may list fewer
+    /// keys, and next_start_after is set in the response while there may be more.
+    DiffSnapshot {
+        label: String,
+        start_after: Option<SnapshotCursor>,
+        limit: Option<u32>,
+    },
+    /// Lists the change log entries after sequence, oldest first. Pass the sequence of the last
+    /// change seen, or 0 to start from the oldest one kept. A gap between sequence and the first
+    /// change returned means entries were pruned before they were read.
+    ChangesSince {
+        sequence: u64,
+        limit: Option<u32>,
+    },
+    /// Returns the root of the Merkle tree over every stored value, see the merkle module. Equal
+    /// roots mean equal values under the same keys, metadata is not covered.
+    StateRoot {},
+    /// Returns the value stored under name together with a proof that the current state root
+    /// commits to it, which merkle::verify checks.
+    /// If name is not specified the default key will be used.
+    GetValueWithProof {
+        name: Option<String>,
     },
 }
 
+/// Position within the changes recorded since a snapshot.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SnapshotCursor {
+    pub snapshot: u64,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderBy {
+    Ascending,
+    Descending,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AggregateOp {
+    Sum,
+    Min,
+    Max,
+    Count,
+}
+
+impl From<OrderBy> for Order {
+    fn from(order: OrderBy) -> Order {
+        match order {
+            OrderBy::Ascending => Order::Ascending,
+            OrderBy::Descending => Order::Descending,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetValueResponse {
     pub name: String,
     pub value: Primitive,
+    pub tags: Vec<String>,
+    /// Only set by GetValue when requested.
+    pub info: Option<KeyInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TypeLockResponse {
+    pub scope: KeyScope,
+    pub lock: Option<TypeLock>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConstraintsResponse {
+    pub scope: KeyScope,
+    pub constraints: Vec<Constraint>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SchemaResponse {
+    pub name_or_prefix: String,
+    /// The registered schema as a JSON Schema document.
+    pub schema: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListSchemasResponse {
+    pub schemas: Vec<SchemaResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportEntry {
+    pub name: String,
+    pub value: Primitive,
+    pub tags: Vec<String>,
+    pub info: Option<KeyInfo>,
+    pub bounds: Option<Bounds>,
+    pub unique: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportStateResponse {
+    pub config: ConfigResponse,
+    pub entries: Vec<ExportEntry>,
+    pub next_start_after: Option<String>,
+}
+
+/// Metadata that is not stored with a value.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Metadata {
+    TypeLock {
+        scope: KeyScope,
+        lock: TypeLock,
+    },
+    Constraints {
+        scope: KeyScope,
+        constraints: Vec<Constraint>,
+    },
+    /// schema is a JSON Schema document, as taken by RegisterSchema.
+    Schema {
+        name_or_prefix: String,
+        schema: String,
+    },
+    Ranked {
+        path: String,
+    },
+}
+
+/// The maps ExportMetadata reads, in the order it reads them.
+#[derive(
+    Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataKind {
+    TypeLock,
+    PrefixTypeLock,
+    Constraints,
+    PrefixConstraints,
+    Schema,
+    Ranked,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MetadataCursor {
+    pub kind: MetadataKind,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ExportMetadataResponse {
+    pub metadata: Vec<Metadata>,
+    pub next_start_after: Option<MetadataCursor>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KeyInfoResponse {
+    pub name: String,
+    pub description: Option<String>,
+    pub unit: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+/// The root hash of the Merkle tree over every stored value.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct StateRootResponse {
+    pub root: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetValueWithProofResponse {
+    pub name: String,
+    pub value: Primitive,
+    /// Hashes of the siblings on the path from the root down to the leaf of the value.
+    pub proof: Vec<Binary>,
+    pub root: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ChangesSinceResponse {
+    pub changes: Vec<Change>,
+    /// Sequence of the latest change made, whether or not it is part of this page.
+    pub latest_sequence: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListSnapshotsResponse {
+    pub snapshots: Vec<Snapshot>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SnapshotDiff {
+    pub name: String,
+    /// None if the key did not exist when the snapshot was taken.
+    pub snapshot_value: Option<Primitive>,
+    /// None if the key was removed since.
+    pub current_value: Option<Primitive>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct DiffSnapshotResponse {
+    pub label: String,
+    pub changes: Vec<SnapshotDiff>,
+    pub next_start_after: Option<SnapshotCursor>,
+}
+
+/// Returned as data by RestoreSnapshot.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RestoreSnapshotResponse {
+    pub label: String,
+    pub restored: u32,
+    pub next_start_after: Option<SnapshotCursor>,
+}
+
+/// Returned in the response data of the arithmetic execute messages.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ArithmeticResponse {
+    pub name: String,
+    pub value: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VecContainsResponse {
+    pub name: String,
+    pub contains: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VecSliceResponse {
+    pub name: String,
+    pub offset: u32,
+    pub values: Vec<Primitive>,
+    /// Length of the whole Vec, so callers know when they have reached the end.
+    pub length: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VecLengthResponse {
+    pub name: String,
+    pub length: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListValuesResponse {
+    pub values: Vec<GetValueResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct FindKeysByValueResponse {
+    pub keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AggregateResponse {
+    pub prefix: String,
+    pub op: AggregateOp,
+    /// The aggregate over this page. Min and Max are None when the page has no values.
+    pub value: Option<Uint128>,
+    /// Number of values aggregated in this page.
+    pub count: u32,
+    pub next_start_after: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RankedValue {
+    pub name: String,
+    pub value: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TopNResponse {
+    pub prefix: String,
+    pub values: Vec<RankedValue>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RankOfResponse {
+    pub name: String,
+    /// The ranked path the rank is relative to.
+    pub prefix: String,
+    pub value: Uint128,
+    pub rank: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListByTagResponse {
+    pub tag: String,
+    pub keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ListChildrenResponse {
+    pub path: String,
+    pub children: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct BoundsResponse {
+    pub name: String,
+    pub min: Option<Uint128>,
+    pub max: Option<Uint128>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct ConfigResponse {
     pub owner: Addr,
+    pub reverse_index: bool,
+    pub key_rules: KeyRules,
+    pub default_key: String,
+    pub limits: Limits,
+    pub import_finished: bool,
+    pub change_retention: Option<u64>,
 }
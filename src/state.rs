@@ -1,14 +1,409 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use cosmwasm_std::{Addr, StdError, Uint128};
-use cw_storage_plus::{Item, Map};
+use cosmwasm_std::{to_vec, Addr, Empty, Order, StdError, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::{
+    Bound, Index, IndexList, IndexedMap, Item, Map, MultiIndex, PrimaryKey, U32Key, U64Key,
+};
+
+use crate::merkle;
 
 pub const DEFAULT_KEY: &str = "default";
+/// Separates the segments of hierarchical key names such as `oracle/btc/price`.
+pub const PATH_SEPARATOR: char = '/';
+/// Default upper bound on the number of ops accepted by a single batch.
+pub const MAX_BATCH_OPS: usize = 50;
+/// Default upper bound on the number of keys removed by a single DeletePrefix.
+pub const MAX_PREFIX_DELETIONS: usize = 50;
+/// Keys starting with this are reserved for values written by the contract itself.
+pub const SYSTEM_PREFIX: &str = "$";
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner: Addr,
+    /// Whether VALUE_KEYS is maintained. Can only be chosen at instantiation.
+    #[serde(default)]
+    pub reverse_index: bool,
+    #[serde(default)]
+    pub key_rules: KeyRules,
+    /// Key used by messages that do not specify a name.
+    #[serde(default = "default_key")]
+    pub default_key: String,
+    #[serde(default)]
+    pub limits: Limits,
+    /// Set by FinishImport, after which ImportState is rejected.
+    #[serde(default)]
+    pub import_finished: bool,
+    /// Number of blocks change log entries are kept for before PruneChanges removes them. Kept
+    /// for good if not set.
+    #[serde(default)]
+    pub change_retention: Option<u64>,
+}
+
+fn default_key() -> String {
+    DEFAULT_KEY.to_string()
+}
+
+/// Bounds on the work done by a single message.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Limits {
+    pub max_batch_ops: u32,
+    /// Also bounds the rank index entries removed when disabling a rank index.
+    pub max_prefix_deletions: u32,
+}
+
+impl Limits {
+    /// A limit of zero would reject every message it bounds.
+    pub fn is_valid(&self) -> bool {
+        self.max_batch_ops > 0 && self.max_prefix_deletions > 0
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_batch_ops: MAX_BATCH_OPS as u32,
+            max_prefix_deletions: MAX_PREFIX_DELETIONS as u32,
+        }
+    }
+}
+
+/// Rules every key name written by an execute message has to follow.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct KeyRules {
+    /// Upper bound on the length of names in bytes.
+    pub max_length: u32,
+    /// Characters allowed besides ASCII letters, digits and the path separator. None allows
+    /// every character.
+    pub allowed_symbols: Option<String>,
+}
+
+impl Default for KeyRules {
+    fn default() -> Self {
+        KeyRules {
+            max_length: 256,
+            allowed_symbols: None,
+        }
+    }
+}
+
+impl KeyRules {
+    /// Returns why name breaks the rules, if it does.
+    pub fn invalid_reason(&self, name: &str) -> Option<String> {
+        if name.is_empty() {
+            return Some("name must not be empty".to_string());
+        }
+        if name.len() > self.max_length as usize {
+            return Some(format!("name is longer than {} bytes", self.max_length));
+        }
+        if name.starts_with(PATH_SEPARATOR) || name.ends_with(PATH_SEPARATOR) {
+            return Some("name must not start or end with a separator".to_string());
+        }
+        if name.split(PATH_SEPARATOR).any(|segment| segment.is_empty()) {
+            return Some("name must not contain empty segments".to_string());
+        }
+        if let Some(symbols) = &self.allowed_symbols {
+            let invalid = name.chars().find(|c| {
+                !c.is_ascii_alphanumeric() && *c != PATH_SEPARATOR && !symbols.contains(*c)
+            });
+            if let Some(c) = invalid {
+                return Some(format!("name must not contain {:?}", c));
+            }
+        }
+        None
+    }
+}
+
+/// Inclusive limits enforced on a key whenever it holds a `Primitive::Uint128`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Bounds {
+    pub min: Option<Uint128>,
+    pub max: Option<Uint128>,
+}
+
+impl Bounds {
+    /// Bounds with min greater than max would reject every value.
+    pub fn is_valid(&self) -> bool {
+        !matches!((self.min, self.max), (Some(min), Some(max)) if min > max)
+    }
+
+    pub fn contains(&self, value: Uint128) -> bool {
+        self.min.iter().all(|min| value >= *min) && self.max.iter().all(|max| value <= *max)
+    }
+}
+
+/// Metadata describing a key for human readers.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct KeyInfo {
+    pub description: Option<String>,
+    /// Unit of the value, such as `ATOM`.
+    pub unit: Option<String>,
+    /// Number of decimals of a Uint128 amount, 6 displays 1500000 as 1.5.
+    pub decimals: Option<u8>,
+}
+
+/// Most decimals KeyInfo can declare, a Uint128 has at most 39 digits.
+pub const MAX_DECIMALS: u8 = 38;
+
+/// The variants of Primitive, without their values.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub enum PrimitiveType {
+    Uint128,
+    String,
+    Bool,
+    Vec,
+}
+
+impl PrimitiveType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PrimitiveType::Uint128 => "Uint128",
+            PrimitiveType::String => "String",
+            PrimitiveType::Bool => "Bool",
+            PrimitiveType::Vec => "Vec",
+        }
+    }
+}
+
+/// Restricts the variant of the values stored under a key, and for Vecs optionally the variant of
+/// their elements.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TypeLock {
+    pub r#type: PrimitiveType,
+    /// Only allowed when type is Vec.
+    pub element_type: Option<PrimitiveType>,
+}
+
+impl TypeLock {
+    pub fn is_valid(&self) -> bool {
+        match self.element_type {
+            Some(element_type) => {
+                self.r#type == PrimitiveType::Vec && element_type != PrimitiveType::Vec
+            }
+            None => true,
+        }
+    }
+
+    /// The locked type as shown in errors, such as `Vec<Uint128>`.
+    pub fn describe(&self) -> String {
+        match self.element_type {
+            Some(element_type) => format!("Vec<{}>", element_type.as_str()),
+            None => self.r#type.as_str().to_string(),
+        }
+    }
+
+    /// Returns the type found in value if the lock rejects it.
+    pub fn mismatch(&self, value: &Primitive) -> Option<String> {
+        match value {
+            Primitive::Vec(vector) if self.r#type == PrimitiveType::Vec => vector
+                .iter()
+                .find_map(|element| self.element_mismatch(element)),
+            _ if value.primitive_type() != self.r#type => {
+                Some(value.primitive_type().as_str().to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the type found if the lock rejects element as part of a Vec.
+    pub fn element_mismatch(&self, element: &Primitive) -> Option<String> {
+        if self.r#type != PrimitiveType::Vec {
+            return Some(PrimitiveType::Vec.as_str().to_string());
+        }
+        match self.element_type {
+            Some(element_type) if element.primitive_type() != element_type => {
+                Some(format!("Vec<{}>", element.primitive_type().as_str()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A business rule on the values stored under a key. Rules on single values apply to every
+/// element when the key holds a Vec, and ignore values of types they do not concern.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Constraint {
+    /// Inclusive limits of Uint128 values.
+    Range {
+        min: Option<Uint128>,
+        max: Option<Uint128>,
+    },
+    /// The value has to equal one of values.
+    OneOf { values: Vec<Primitive> },
+    /// Upper bound on the length of Strings in bytes.
+    MaxLength { max: u32 },
+    /// Upper bound on the length of Strings in characters, the way JSON Schema counts them.
+    MaxChars { max: u32 },
+    /// Upper bound on the number of elements of a Vec.
+    MaxItems { max: u32 },
+    /// Strings have to match pattern, where `*` matches any sequence of characters and `?` a
+    /// single character.
+    Pattern { pattern: String },
+}
+
+impl Constraint {
+    /// Returns true if the single value, or element of a Vec, satisfies the rule. MaxItems
+    /// is checked against the length instead.
+    pub fn allows(&self, value: &Primitive) -> bool {
+        match (self, value) {
+            (Constraint::Range { min, max }, Primitive::Uint128(value)) => Bounds {
+                min: *min,
+                max: *max,
+            }
+            .contains(*value),
+            (Constraint::OneOf { values }, value) => values.contains(value),
+            (Constraint::MaxLength { max }, Primitive::String(value)) => {
+                value.len() <= *max as usize
+            }
+            (Constraint::MaxChars { max }, Primitive::String(value)) => {
+                value.chars().count() <= *max as usize
+            }
+            (Constraint::Pattern { pattern }, Primitive::String(value)) => {
+                matches_pattern(pattern, value)
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns true if a Vec of len elements satisfies the rule.
+    pub fn allows_len(&self, len: u32) -> bool {
+        match self {
+            Constraint::MaxItems { max } => len <= *max,
+            _ => true,
+        }
+    }
+}
+
+/// The JSON Schema types matching the Primitive variants.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaType {
+    Integer,
+    String,
+    Boolean,
+    Array,
+}
+
+impl SchemaType {
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match self {
+            SchemaType::Integer => PrimitiveType::Uint128,
+            SchemaType::String => PrimitiveType::String,
+            SchemaType::Boolean => PrimitiveType::Bool,
+            SchemaType::Array => PrimitiveType::Vec,
+        }
+    }
+}
+
+/// The expected shape of a key, read from a JSON Schema document by json_schema::parse.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct KeySchema {
+    #[serde(default)]
+    pub r#type: Option<SchemaType>,
+    #[serde(default)]
+    pub r#enum: Option<Vec<Primitive>>,
+    #[serde(default)]
+    pub minimum: Option<Uint128>,
+    #[serde(default)]
+    pub maximum: Option<Uint128>,
+    #[serde(default, rename = "maxLength")]
+    pub max_length: Option<u32>,
+    /// The schema of the elements of an array.
+    #[serde(default)]
+    pub items: Option<Box<KeySchema>>,
+}
+
+impl KeySchema {
+    /// Returns why the schema cannot be registered, if it cannot.
+    pub fn invalid_reason(&self) -> Option<&'static str> {
+        if self.r#enum.iter().flatten().any(|value| !value.is_scalar()) {
+            return Some("enum can only list single values");
+        }
+        if matches!((self.minimum, self.maximum), (Some(min), Some(max)) if min > max) {
+            return Some("minimum must not be greater than maximum");
+        }
+        if self.r#type == Some(SchemaType::Array) && !self.constraints().is_empty() {
+            return Some("enum, minimum, maximum and maxLength of arrays belong in items");
+        }
+        match &self.items {
+            Some(_) if self.r#type != Some(SchemaType::Array) => {
+                Some("items is only allowed for the array type")
+            }
+            Some(items) if items.r#type == Some(SchemaType::Array) || items.items.is_some() => {
+                Some("arrays cannot be nested")
+            }
+            Some(items) => items.invalid_reason(),
+            None => None,
+        }
+    }
+
+    /// The type restriction of the schema, including that of array elements.
+    pub fn type_lock(&self) -> Option<TypeLock> {
+        self.r#type.map(|schema_type| TypeLock {
+            r#type: schema_type.primitive_type(),
+            element_type: self
+                .items
+                .as_ref()
+                .and_then(|items| items.r#type)
+                .map(|items_type| items_type.primitive_type()),
+        })
+    }
+
+    /// The rules on single values, which for arrays are set in items instead.
+    pub fn constraints(&self) -> Vec<Constraint> {
+        let mut constraints = vec![];
+        if self.minimum.is_some() || self.maximum.is_some() {
+            constraints.push(Constraint::Range {
+                min: self.minimum,
+                max: self.maximum,
+            });
+        }
+        if let Some(values) = &self.r#enum {
+            constraints.push(Constraint::OneOf {
+                values: values.clone(),
+            });
+        }
+        if let Some(max) = self.max_length {
+            constraints.push(Constraint::MaxChars { max });
+        }
+        constraints
+    }
+
+    /// The rules on the elements of arrays.
+    pub fn item_constraints(&self) -> Vec<Constraint> {
+        self.items
+            .as_ref()
+            .map(|items| items.constraints())
+            .unwrap_or_default()
+    }
+}
+
+/// Matches value against a pattern of literal characters and the `*` and `?` wildcards.
+pub fn matches_pattern(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+    let (mut p, mut v) = (0, 0);
+    // Position of the last `*` and of the value when it was reached, to backtrack to.
+    let mut star: Option<(usize, usize)> = None;
+    while v < value.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == value[v]) {
+            p += 1;
+            v += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, v));
+            p += 1;
+        } else if let Some((star_p, star_v)) = star {
+            // Let the last `*` match one more character.
+            p = star_p + 1;
+            v = star_v + 1;
+            star = Some((star_p, star_v + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -39,6 +434,35 @@ impl Primitive {
         }
     }
 
+    pub fn is_scalar(&self) -> bool {
+        !matches!(self, Primitive::Vec(_))
+    }
+
+    /// Hash of the serialized value, used to index keys by the value they hold.
+    pub fn hash(&self) -> StdResult<Vec<u8>> {
+        Ok(Sha256::digest(&to_vec(self)?).to_vec())
+    }
+
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match self {
+            Primitive::Uint128(_) => PrimitiveType::Uint128,
+            Primitive::String(_) => PrimitiveType::String,
+            Primitive::Bool(_) => PrimitiveType::Bool,
+            Primitive::Vec(_) => PrimitiveType::Vec,
+        }
+    }
+
+    /// Returns true if this is a Vec that holds the same element more than once.
+    pub fn has_duplicates(&self) -> bool {
+        match self {
+            Primitive::Vec(vector) => vector
+                .iter()
+                .enumerate()
+                .any(|(i, p)| vector[i + 1..].contains(p)),
+            _ => false,
+        }
+    }
+
     pub fn try_get_uint128(&self) -> Result<Uint128, StdError> {
         match self {
             Primitive::Uint128(value) => Ok(*value),
@@ -68,12 +492,555 @@ impl Primitive {
     }
 }
 
-pub const DATA: Map<&str, Primitive> = Map::new("data");
+pub struct DataIndexes<'a> {
+    pub primitive_type: MultiIndex<'a, (Vec<u8>, Vec<u8>), Primitive>,
+}
+
+impl<'a> IndexList<Primitive> for DataIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Primitive>> + '_> {
+        let v: Vec<&dyn Index<Primitive>> = vec![&self.primitive_type];
+        Box::new(v.into_iter())
+    }
+}
+
+/// Every stored value by key, indexed by the variant of the Primitive.
+pub fn data<'a>() -> IndexedMap<'a, &'a str, Primitive, DataIndexes<'a>> {
+    let indexes = DataIndexes {
+        primitive_type: MultiIndex::new(
+            |value, pk| (value.primitive_type().as_str().as_bytes().to_vec(), pk),
+            "data",
+            "data__type",
+        ),
+    };
+    IndexedMap::new("data", indexes)
+}
+
+/// Upper bound on the number of tags of a single key.
+pub const MAX_TAGS: usize = 16;
+
+pub struct TagIndexes<'a> {
+    pub tag: MultiIndex<'a, (Vec<u8>, Vec<u8>), String>,
+}
+
+impl<'a> IndexList<String> for TagIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<String>> + '_> {
+        let v: Vec<&dyn Index<String>> = vec![&self.tag];
+        Box::new(v.into_iter())
+    }
+}
+
+/// The tags of every key by (key, tag), holding the tag again so that it can be indexed.
+pub fn tags<'a>() -> IndexedMap<'a, (&'a str, &'a str), String, TagIndexes<'a>> {
+    let indexes = TagIndexes {
+        tag: MultiIndex::new(|tag, pk| (tag.as_bytes().to_vec(), pk), "tags", "tags__tag"),
+    };
+    IndexedMap::new("tags", indexes)
+}
+
+/// Returns the tags of name in ascending order.
+pub fn load_tags(storage: &dyn Storage, name: &str) -> StdResult<Vec<String>> {
+    tags()
+        .prefix(name)
+        .keys(storage, None, None, Order::Ascending)
+        .map(|tag| String::from_utf8(tag).map_err(StdError::from))
+        .collect()
+}
+
+/// Returns the key of a primary key of tags(), as found in the tag index.
+pub fn parse_tag_key(key: &[u8]) -> StdResult<String> {
+    let invalid = || StdError::generic_err("Invalid tag index entry");
+    if key.len() < 2 {
+        return Err(invalid());
+    }
+    let len = u16::from_be_bytes([key[0], key[1]]) as usize;
+    let name = key.get(2..2 + len).ok_or_else(invalid)?;
+    Ok(String::from_utf8(name.to_vec())?)
+}
+
 pub const CONFIG: Item<Config> = Item::new("config");
+pub const BOUNDS: Map<&str, Bounds> = Map::new("bounds");
+pub const KEY_INFO: Map<&str, KeyInfo> = Map::new("key_info");
+/// Type locks of single keys, kept when the key is deleted.
+pub const TYPE_LOCKS: Map<&str, TypeLock> = Map::new("type_locks");
+/// Type locks of every key below a directory prefix. The empty prefix covers the whole store.
+pub const PREFIX_TYPE_LOCKS: Map<&str, TypeLock> = Map::new("prefix_type_locks");
+/// Constraints of single keys, kept when the key is deleted.
+pub const CONSTRAINTS: Map<&str, Vec<Constraint>> = Map::new("constraints");
+/// Constraints of every key below a directory prefix. The empty prefix covers the whole store.
+pub const PREFIX_CONSTRAINTS: Map<&str, Vec<Constraint>> = Map::new("prefix_constraints");
+/// Schemas by the key they were registered for. Names ending with PATH_SEPARATOR apply to every key
+/// starting with them.
+pub const SCHEMAS: Map<&str, KeySchema> = Map::new("schemas");
+/// Keys whose Vec must not contain duplicate elements.
+pub const UNIQUE_KEYS: Map<&str, bool> = Map::new("unique_keys");
+
+/// Keys by the hash of the scalar value they hold, only maintained when Config::reverse_index is
+/// set.
+pub const VALUE_KEYS: Map<(&[u8], &str), Empty> = Map::new("value_keys");
+
+/// Directory prefixes whose Uint128 values are kept in RANK_INDEX. The empty prefix covers the
+/// whole store.
+pub const RANKED_PREFIXES: Map<&str, Empty> = Map::new("ranked_prefixes");
+/// Ranked prefixes that held keys when ranking was enabled and that RebuildIndexes has not
+/// scanned completely yet, with the last key below the prefix it indexed. TopN and RankOf
+/// refuse to answer for them, as their results would miss those keys.
+pub const RANK_BACKFILLS: Map<&str, Option<String>> = Map::new("rank_backfills");
+/// Keys holding a Uint128 by ranked prefix, ordered by value and then by key. See rank_key.
+pub const RANK_INDEX: Map<(&str, &[u8]), Empty> = Map::new("rank_index");
+
+/// The big endian value followed by the key, so that entries sort by value first.
+pub fn rank_key(value: Uint128, name: &str) -> Vec<u8> {
+    let mut key = value.u128().to_be_bytes().to_vec();
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// Splits an entry of RANK_INDEX back into the value and the key.
+pub fn parse_rank_key(key: &[u8]) -> StdResult<(Uint128, String)> {
+    if key.len() < 16 {
+        return Err(StdError::generic_err("Invalid rank index entry"));
+    }
+    let (value, name) = key.split_at(16);
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(value);
+    Ok((
+        Uint128::new(u128::from_be_bytes(bytes)),
+        String::from_utf8(name.to_vec())?,
+    ))
+}
+
+/// A checkpoint of every value taken by CreateSnapshot. Nothing is copied when it is taken, the
+/// first change of each key afterwards stores the value it replaces in SNAPSHOT_CHANGES instead.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Snapshot {
+    pub id: u64,
+    pub label: String,
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+/// Id of the latest snapshot taken, ids start at 1 and are not reused after deletion.
+pub const SNAPSHOT_COUNT: Item<u64> = Item::new("snapshot_count");
+pub const SNAPSHOTS: Map<U64Key, Snapshot> = Map::new("snapshots");
+pub const SNAPSHOT_LABELS: Map<&str, u64> = Map::new("snapshot_labels");
+/// The value a key held before its first change after each snapshot, None if it did not exist.
+pub const SNAPSHOT_CHANGES: Map<(U64Key, &str), Option<Primitive>> = Map::new("snapshot_changes");
+/// The same entries as SNAPSHOT_CHANGES, ordered by key first.
+pub const SNAPSHOT_CHANGED_KEYS: Map<(&str, U64Key), Empty> = Map::new("snapshot_changed_keys");
+
+/// Saves the value stored under name for the latest snapshot, unless it already changed since
+/// then. Has to be called before every change of a value.
+pub fn record_change(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    let id = SNAPSHOT_COUNT.may_load(storage)?.unwrap_or_default();
+    // Deleting the latest snapshot leaves none to record changes for.
+    if id == 0
+        || !SNAPSHOTS.has(storage, U64Key::new(id))
+        || SNAPSHOT_CHANGES.has(storage, (U64Key::new(id), name))
+    {
+        return Ok(());
+    }
+    let old = load_primitive(storage, name)?;
+    SNAPSHOT_CHANGES.save(storage, (U64Key::new(id), name), &old)?;
+    SNAPSHOT_CHANGED_KEYS.save(storage, (name, U64Key::new(id)), &Empty {})
+}
+
+/// The raw key of an entry of SNAPSHOT_CHANGES, usable as a range bound. An empty name sorts
+/// before every entry of the snapshot.
+pub fn change_key(id: u64, name: &str) -> Vec<u8> {
+    (U64Key::new(id), name).joined_key()
+}
+
+/// Splits a raw key of SNAPSHOT_CHANGES back into the snapshot id and the key.
+pub fn parse_change_key(key: &[u8]) -> StdResult<(u64, String)> {
+    // The id is length prefixed as the first element of the tuple.
+    if key.len() < 10 {
+        return Err(StdError::generic_err("Invalid snapshot change key"));
+    }
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&key[2..10]);
+    Ok((
+        u64::from_be_bytes(bytes),
+        String::from_utf8(key[10..].to_vec())?,
+    ))
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeOp {
+    /// The key was written, including changes to single elements of a Vec.
+    Set,
+    Delete,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Change {
+    pub sequence: u64,
+    pub height: u64,
+    pub name: String,
+    pub op: ChangeOp,
+    pub writer: Addr,
+}
+
+/// Sequence number of the latest change, sequence numbers start at 1 and are never reused.
+pub const CHANGE_SEQUENCE: Item<u64> = Item::new("change_sequence");
+/// Every change of a value in the order they were made, minus those removed by PruneChanges.
+pub const CHANGE_LOG: Map<U64Key, Change> = Map::new("change_log");
+
+/// Appends a change of the key name to CHANGE_LOG. Has to be called after every change of a
+/// value.
+pub fn log_change(
+    storage: &mut dyn Storage,
+    height: u64,
+    writer: &Addr,
+    name: &str,
+    op: ChangeOp,
+) -> StdResult<()> {
+    let sequence = CHANGE_SEQUENCE.may_load(storage)?.unwrap_or_default() + 1;
+    let change = Change {
+        sequence,
+        height,
+        name: name.to_string(),
+        op,
+        writer: writer.clone(),
+    };
+    CHANGE_LOG.save(storage, U64Key::new(sequence), &change)?;
+    CHANGE_SEQUENCE.save(storage, &sequence)
+}
+
+/// Vecs longer than this are stored one element per entry instead of as a single blob, so that
+/// pushing, popping and reading a single element does not rewrite the whole Vec.
+pub const VEC_CHUNK_THRESHOLD: u32 = 32;
+/// Length of every Vec stored element per entry. data() keeps an empty Vec under these keys as a
+/// placeholder so that every key can still be found in it.
+pub const VEC_LENGTHS: Map<&str, u32> = Map::new("vec_lengths");
+pub const VEC_ELEMENTS: Map<(&str, U32Key), Primitive> = Map::new("vec_elements");
+
+/// Loads the value stored under name, reassembling it if it is a chunked Vec. All reads of stored
+/// values should go through here.
+pub fn load_primitive(storage: &dyn Storage, name: &str) -> StdResult<Option<Primitive>> {
+    match data().may_load(storage, name)? {
+        Some(value) => Ok(Some(resolve_primitive(storage, name, value)?)),
+        None => Ok(None),
+    }
+}
+
+/// Turns a value read straight from data(), for example while iterating over it, into the value
+/// stored under name.
+pub fn resolve_primitive(
+    storage: &dyn Storage,
+    name: &str,
+    value: Primitive,
+) -> StdResult<Primitive> {
+    // Only the empty Vec placeholder can stand in for a chunked Vec.
+    if !matches!(&value, Primitive::Vec(vector) if vector.is_empty()) {
+        return Ok(value);
+    }
+    match VEC_LENGTHS.may_load(storage, name)? {
+        Some(len) => Ok(Primitive::Vec(load_elements(storage, name, 0, len)?)),
+        None => Ok(value),
+    }
+}
+
+/// Stores value under name, picking the layout based on its size and cleaning up whatever the
+/// previous layout left behind.
+pub fn save_primitive(storage: &mut dyn Storage, name: &str, value: &Primitive) -> StdResult<()> {
+    match value {
+        Primitive::Vec(vector) if vector.len() > VEC_CHUNK_THRESHOLD as usize => {
+            // Whatever is stored inline is simply overwritten, only chunks need cleaning up.
+            let layout = match VEC_LENGTHS.may_load(storage, name)? {
+                Some(len) => VecLayout::Chunked { len },
+                None => VecLayout::Inline(vec![]),
+            };
+            let mut stored = StoredVec { name, layout };
+            stored.replace(storage, vector)?;
+            stored.save(storage)
+        }
+        _ => {
+            remove_elements(storage, name)?;
+            data().save(storage, name, value)
+        }
+    }
+}
+
+pub fn remove_primitive(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    remove_elements(storage, name)?;
+    data().remove(storage, name)
+}
+
+fn load_elements(
+    storage: &dyn Storage,
+    name: &str,
+    offset: u32,
+    limit: u32,
+) -> StdResult<Vec<Primitive>> {
+    VEC_ELEMENTS
+        .prefix(name)
+        .range(
+            storage,
+            Some(Bound::inclusive_int(offset)),
+            None,
+            Order::Ascending,
+        )
+        .take(limit as usize)
+        .map(|item| item.map(|(_, value)| value))
+        .collect()
+}
+
+fn remove_elements(storage: &mut dyn Storage, name: &str) -> StdResult<()> {
+    if let Some(len) = VEC_LENGTHS.may_load(storage, name)? {
+        for index in 0..len {
+            VEC_ELEMENTS.remove(storage, (name, U32Key::new(index)));
+        }
+        merkle::clear_elements(storage, name, len)?;
+        VEC_LENGTHS.remove(storage, name);
+    }
+    Ok(())
+}
+
+enum VecLayout {
+    Inline(Vec<Primitive>),
+    Chunked { len: u32 },
+}
+
+/// A Vec stored under a key, hiding whether it is kept inline in data() or chunked. Changes to a
+/// chunked Vec are written immediately, together with its element tree in the Merkle tree,
+/// while an inline Vec is only written by `save`.
+pub struct StoredVec<'a> {
+    name: &'a str,
+    layout: VecLayout,
+}
+
+impl<'a> StoredVec<'a> {
+    /// A missing key is treated as an empty Vec.
+    pub fn load(storage: &dyn Storage, name: &'a str) -> StdResult<Self> {
+        let layout = match VEC_LENGTHS.may_load(storage, name)? {
+            Some(len) => VecLayout::Chunked { len },
+            None => match data().may_load(storage, name)? {
+                Some(primitive) => VecLayout::Inline(primitive.try_get_vec()?),
+                None => VecLayout::Inline(vec![]),
+            },
+        };
+        Ok(StoredVec { name, layout })
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn len(&self) -> u32 {
+        match &self.layout {
+            VecLayout::Inline(vector) => vector.len() as u32,
+            VecLayout::Chunked { len } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        matches!(self.layout, VecLayout::Chunked { .. })
+    }
+
+    pub fn get(&self, storage: &dyn Storage, index: u32) -> StdResult<Option<Primitive>> {
+        match &self.layout {
+            VecLayout::Inline(vector) => Ok(vector.get(index as usize).cloned()),
+            VecLayout::Chunked { .. } => {
+                VEC_ELEMENTS.may_load(storage, (self.name, U32Key::new(index)))
+            }
+        }
+    }
+
+    /// Returns at most limit elements starting at offset.
+    pub fn slice(
+        &self,
+        storage: &dyn Storage,
+        offset: u32,
+        limit: u32,
+    ) -> StdResult<Vec<Primitive>> {
+        match &self.layout {
+            VecLayout::Inline(vector) => Ok(vector
+                .iter()
+                .skip(offset as usize)
+                .take(limit as usize)
+                .cloned()
+                .collect()),
+            VecLayout::Chunked { .. } => load_elements(storage, self.name, offset, limit),
+        }
+    }
+
+    pub fn position(&self, storage: &dyn Storage, value: &Primitive) -> StdResult<Option<u32>> {
+        match &self.layout {
+            VecLayout::Inline(vector) => {
+                Ok(vector.iter().position(|p| p == value).map(|i| i as u32))
+            }
+            VecLayout::Chunked { len } => {
+                for (index, element) in load_elements(storage, self.name, 0, *len)?
+                    .iter()
+                    .enumerate()
+                {
+                    if element == value {
+                        return Ok(Some(index as u32));
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    pub fn contains(&self, storage: &dyn Storage, value: &Primitive) -> StdResult<bool> {
+        Ok(self.position(storage, value)?.is_some())
+    }
+
+    /// The hash the Merkle tree commits to for the Vec, without loading a chunked one in full.
+    pub fn value_hash(&self, storage: &dyn Storage) -> StdResult<merkle::Hash> {
+        match &self.layout {
+            VecLayout::Inline(vector) => merkle::vec_hash(vector),
+            VecLayout::Chunked { len } => merkle::stored_vec_hash(storage, self.name, *len),
+        }
+    }
+
+    pub fn push(&mut self, storage: &mut dyn Storage, value: Primitive) -> StdResult<()> {
+        match &mut self.layout {
+            VecLayout::Inline(vector) => vector.push(value),
+            VecLayout::Chunked { len } => {
+                VEC_ELEMENTS.save(storage, (self.name, U32Key::new(*len)), &value)?;
+                merkle::set_element(storage, self.name, *len, &value, *len + 1)?;
+                *len += 1;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn pop(&mut self, storage: &mut dyn Storage) -> StdResult<Option<Primitive>> {
+        match &mut self.layout {
+            VecLayout::Inline(vector) => Ok(vector.pop()),
+            VecLayout::Chunked { len } => {
+                if *len == 0 {
+                    return Ok(None);
+                }
+                *len -= 1;
+                let key = (self.name, U32Key::new(*len));
+                let value = VEC_ELEMENTS.load(storage, key.clone())?;
+                VEC_ELEMENTS.remove(storage, key);
+                merkle::pop_element(storage, self.name, *len)?;
+                Ok(Some(value))
+            }
+        }
+    }
+
+    /// Replaces the element at index, which must be in range, and returns the old element.
+    pub fn set(
+        &mut self,
+        storage: &mut dyn Storage,
+        index: u32,
+        value: Primitive,
+    ) -> StdResult<Primitive> {
+        match &mut self.layout {
+            VecLayout::Inline(vector) => Ok(std::mem::replace(&mut vector[index as usize], value)),
+            VecLayout::Chunked { len } => {
+                let key = (self.name, U32Key::new(index));
+                let old = VEC_ELEMENTS.load(storage, key.clone())?;
+                VEC_ELEMENTS.save(storage, key, &value)?;
+                merkle::set_element(storage, self.name, index, &value, *len)?;
+                Ok(old)
+            }
+        }
+    }
+
+    /// Inserts value at index, which may equal the length. Chunked Vecs have to shift every
+    /// element after index.
+    pub fn insert(
+        &mut self,
+        storage: &mut dyn Storage,
+        index: u32,
+        value: Primitive,
+    ) -> StdResult<()> {
+        match &mut self.layout {
+            VecLayout::Inline(vector) => vector.insert(index as usize, value),
+            VecLayout::Chunked { len } => {
+                for i in (index..*len).rev() {
+                    let element = VEC_ELEMENTS.load(storage, (self.name, U32Key::new(i)))?;
+                    VEC_ELEMENTS.save(storage, (self.name, U32Key::new(i + 1)), &element)?;
+                }
+                VEC_ELEMENTS.save(storage, (self.name, U32Key::new(index)), &value)?;
+                *len += 1;
+                // Every element after index moved, so the element tree is rebuilt.
+                let elements = load_elements(storage, self.name, 0, *len)?;
+                merkle::set_elements(storage, self.name, *len - 1, &elements)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the element at index, which must be in range. Chunked Vecs have to shift every
+    /// element after index.
+    pub fn remove(&mut self, storage: &mut dyn Storage, index: u32) -> StdResult<Primitive> {
+        match &mut self.layout {
+            VecLayout::Inline(vector) => Ok(vector.remove(index as usize)),
+            VecLayout::Chunked { len } => {
+                let removed = VEC_ELEMENTS.load(storage, (self.name, U32Key::new(index)))?;
+                for i in index + 1..*len {
+                    let element = VEC_ELEMENTS.load(storage, (self.name, U32Key::new(i)))?;
+                    VEC_ELEMENTS.save(storage, (self.name, U32Key::new(i - 1)), &element)?;
+                }
+                *len -= 1;
+                VEC_ELEMENTS.remove(storage, (self.name, U32Key::new(*len)));
+                let elements = load_elements(storage, self.name, 0, *len)?;
+                merkle::set_elements(storage, self.name, *len + 1, &elements)?;
+                Ok(removed)
+            }
+        }
+    }
+
+    /// Replaces every element with the given ones.
+    pub fn replace(&mut self, storage: &mut dyn Storage, elements: &[Primitive]) -> StdResult<()> {
+        match &mut self.layout {
+            VecLayout::Inline(vector) => *vector = elements.to_vec(),
+            VecLayout::Chunked { len } => {
+                for (index, element) in elements.iter().enumerate() {
+                    VEC_ELEMENTS.save(storage, (self.name, U32Key::new(index as u32)), element)?;
+                }
+                for index in elements.len() as u32..*len {
+                    VEC_ELEMENTS.remove(storage, (self.name, U32Key::new(index)));
+                }
+                merkle::set_elements(storage, self.name, *len, elements)?;
+                *len = elements.len() as u32;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the Vec back, moving an inline Vec to the chunked layout once it grows past
+    /// VEC_CHUNK_THRESHOLD. Chunked Vecs stay chunked when they shrink.
+    pub fn save(self, storage: &mut dyn Storage) -> StdResult<()> {
+        let len = match self.layout {
+            VecLayout::Inline(vector) => {
+                if vector.len() <= VEC_CHUNK_THRESHOLD as usize {
+                    return data().save(storage, self.name, &Primitive::Vec(vector));
+                }
+                for (index, element) in vector.iter().enumerate() {
+                    VEC_ELEMENTS.save(storage, (self.name, U32Key::new(index as u32)), element)?;
+                }
+                merkle::set_elements(storage, self.name, 0, &vector)?;
+                vector.len() as u32
+            }
+            VecLayout::Chunked { len } => len,
+        };
+        VEC_LENGTHS.save(storage, self.name, &len)?;
+        data().save(storage, self.name, &Primitive::Vec(vec![]))
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::json_schema;
+    use cosmwasm_std::testing::MockStorage;
+
+    fn numbers(range: std::ops::Range<u128>) -> Vec<Primitive> {
+        range.map(|i| Primitive::Uint128(Uint128::new(i))).collect()
+    }
 
     #[test]
     fn test_parse_error() {
@@ -87,6 +1054,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::unnecessary_cast)]
     fn try_get_uint128() {
         let primitive = Primitive::Uint128(Uint128::from(5 as u128));
         assert_eq!(
@@ -114,6 +1082,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn try_get_bool() {
         let primitive = Primitive::Bool(true);
         assert_eq!(true, primitive.try_get_bool().unwrap());
@@ -140,6 +1109,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn bounds_contains() {
+        let bounds = Bounds {
+            min: Some(Uint128::new(5)),
+            max: Some(Uint128::new(10)),
+        };
+        assert!(!bounds.contains(Uint128::new(4)));
+        assert!(bounds.contains(Uint128::new(5)));
+        assert!(bounds.contains(Uint128::new(10)));
+        assert!(!bounds.contains(Uint128::new(11)));
+
+        let bounds = Bounds {
+            min: None,
+            max: None,
+        };
+        assert!(bounds.contains(Uint128::zero()));
+        assert!(bounds.contains(Uint128::MAX));
+    }
+
+    #[test]
+    fn has_duplicates() {
+        let primitive = Primitive::Vec(vec![Primitive::Bool(true), Primitive::Bool(false)]);
+        assert!(!primitive.has_duplicates());
+
+        let primitive = Primitive::Vec(vec![
+            Primitive::String("a".to_string()),
+            Primitive::Bool(true),
+            Primitive::String("a".to_string()),
+        ]);
+        assert!(primitive.has_duplicates());
+
+        let primitive = Primitive::String("aa".to_string());
+        assert!(!primitive.has_duplicates());
+    }
+
+    #[test]
+    fn hash_depends_on_type_and_value() {
+        let hash = Primitive::String("1".to_string()).hash().unwrap();
+        assert_eq!(32, hash.len());
+        assert_eq!(hash, Primitive::String("1".to_string()).hash().unwrap());
+        assert_ne!(hash, Primitive::String("2".to_string()).hash().unwrap());
+        assert_ne!(hash, Primitive::Uint128(Uint128::new(1)).hash().unwrap());
+    }
+
     #[test]
     fn is_valid() {
         let valid_primitive = Primitive::Bool(true);
@@ -161,4 +1174,330 @@ mod tests {
         let invalid_primitive = Primitive::Vec(vec![Primitive::Bool(true), Primitive::Vec(vec![])]);
         assert!(invalid_primitive.is_invalid());
     }
+
+    #[test]
+    fn large_vectors_are_chunked() {
+        let mut storage = MockStorage::new();
+        let len = VEC_CHUNK_THRESHOLD as u128 + 1;
+
+        save_primitive(&mut storage, "vec", &Primitive::Vec(numbers(0..len))).unwrap();
+        assert_eq!(
+            Some(len as u32),
+            VEC_LENGTHS.may_load(&storage, "vec").unwrap()
+        );
+        assert_eq!(
+            Primitive::Vec(vec![]),
+            data().load(&storage, "vec").unwrap()
+        );
+        assert_eq!(
+            Some(Primitive::Vec(numbers(0..len))),
+            load_primitive(&storage, "vec").unwrap()
+        );
+
+        // Shrinking with a plain write moves it back inline and drops every chunk.
+        save_primitive(&mut storage, "vec", &Primitive::Vec(numbers(0..2))).unwrap();
+        assert!(!VEC_LENGTHS.has(&storage, "vec"));
+        assert!(VEC_ELEMENTS
+            .prefix("vec")
+            .range(&storage, None, None, Order::Ascending)
+            .next()
+            .is_none());
+        assert_eq!(
+            Some(Primitive::Vec(numbers(0..2))),
+            load_primitive(&storage, "vec").unwrap()
+        );
+
+        save_primitive(&mut storage, "vec", &Primitive::Vec(numbers(0..len))).unwrap();
+        remove_primitive(&mut storage, "vec").unwrap();
+        assert_eq!(None, load_primitive(&storage, "vec").unwrap());
+        assert!(!VEC_LENGTHS.has(&storage, "vec"));
+        assert!(!VEC_ELEMENTS.has(&storage, ("vec", U32Key::new(0))));
+    }
+
+    #[test]
+    fn stored_vec_switches_layout_when_growing() {
+        let mut storage = MockStorage::new();
+        let threshold = VEC_CHUNK_THRESHOLD as u128;
+
+        save_primitive(&mut storage, "vec", &Primitive::Vec(numbers(0..threshold))).unwrap();
+        let mut vector = StoredVec::load(&storage, "vec").unwrap();
+        assert!(!vector.is_chunked());
+        vector
+            .push(&mut storage, Primitive::Uint128(Uint128::new(threshold)))
+            .unwrap();
+        vector.save(&mut storage).unwrap();
+
+        let vector = StoredVec::load(&storage, "vec").unwrap();
+        assert!(vector.is_chunked());
+        assert_eq!(threshold as u32 + 1, vector.len());
+        assert_eq!(
+            Some(Primitive::Uint128(Uint128::new(threshold))),
+            vector.get(&storage, threshold as u32).unwrap()
+        );
+        assert_eq!(
+            numbers(0..threshold + 1),
+            load_primitive(&storage, "vec")
+                .unwrap()
+                .unwrap()
+                .try_get_vec()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn chunked_vec_operations() {
+        let mut storage = MockStorage::new();
+        let len = VEC_CHUNK_THRESHOLD as u128 + 2;
+        let mut expected = numbers(0..len);
+        save_primitive(&mut storage, "vec", &Primitive::Vec(expected.clone())).unwrap();
+
+        let mut vector = StoredVec::load(&storage, "vec").unwrap();
+        assert!(vector.is_chunked());
+
+        let value = Primitive::Bool(true);
+        vector.push(&mut storage, value.clone()).unwrap();
+        expected.push(value.clone());
+        assert_eq!(Some(len as u32), vector.position(&storage, &value).unwrap());
+
+        assert_eq!(Some(value.clone()), vector.pop(&mut storage).unwrap());
+        expected.pop();
+        assert!(!vector.contains(&storage, &value).unwrap());
+
+        let value = Primitive::String("inserted".to_string());
+        vector.insert(&mut storage, 3, value.clone()).unwrap();
+        expected.insert(3, value);
+
+        let removed = vector.remove(&mut storage, 10).unwrap();
+        assert_eq!(expected.remove(10), removed);
+
+        let value = Primitive::Bool(false);
+        let old = vector.set(&mut storage, 0, value.clone()).unwrap();
+        assert_eq!(std::mem::replace(&mut expected[0], value), old);
+
+        assert_eq!(
+            expected[5..8].to_vec(),
+            vector.slice(&storage, 5, 3).unwrap()
+        );
+        vector.save(&mut storage).unwrap();
+
+        assert_eq!(
+            Some(Primitive::Vec(expected.clone())),
+            load_primitive(&storage, "vec").unwrap()
+        );
+        assert_eq!(
+            expected.len() as u32,
+            VEC_LENGTHS.load(&storage, "vec").unwrap()
+        );
+        assert!(!VEC_ELEMENTS.has(&storage, ("vec", U32Key::new(expected.len() as u32))));
+    }
+
+    #[test]
+    fn rank_keys_sort_by_value() {
+        let low = rank_key(Uint128::new(2), "b");
+        let high = rank_key(Uint128::new(256), "a");
+        assert!(low < high);
+        assert_eq!(
+            (Uint128::new(256), "a".to_string()),
+            parse_rank_key(&high).unwrap()
+        );
+        assert!(parse_rank_key(&[1, 2]).is_err());
+    }
+
+    #[test]
+    fn type_lock_mismatch() {
+        let uint = TypeLock {
+            r#type: PrimitiveType::Uint128,
+            element_type: None,
+        };
+        let strings = TypeLock {
+            r#type: PrimitiveType::Vec,
+            element_type: Some(PrimitiveType::String),
+        };
+        let any_vec = TypeLock {
+            r#type: PrimitiveType::Vec,
+            element_type: None,
+        };
+        let nested = TypeLock {
+            r#type: PrimitiveType::Vec,
+            element_type: Some(PrimitiveType::Vec),
+        };
+        let not_vec = TypeLock {
+            r#type: PrimitiveType::Bool,
+            element_type: Some(PrimitiveType::Bool),
+        };
+        assert!(uint.is_valid() && strings.is_valid() && any_vec.is_valid());
+        assert!(!nested.is_valid() && !not_vec.is_valid());
+        assert_eq!("Uint128", uint.describe());
+        assert_eq!("Vec<String>", strings.describe());
+
+        let string = Primitive::String("a".to_string());
+        let mixed = Primitive::Vec(vec![string.clone(), Primitive::Bool(true)]);
+        assert_eq!(None, uint.mismatch(&Primitive::Uint128(Uint128::zero())));
+        assert_eq!(Some("String".to_string()), uint.mismatch(&string));
+        assert_eq!(Some("Vec<Bool>".to_string()), strings.mismatch(&mixed));
+        assert_eq!(None, any_vec.mismatch(&mixed));
+        assert_eq!(Some("Vec".to_string()), uint.element_mismatch(&string));
+        assert_eq!(None, strings.element_mismatch(&string));
+    }
+
+    #[test]
+    fn pattern_wildcards() {
+        assert!(matches_pattern("", ""));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("cosmos1*", "cosmos1abc"));
+        assert!(matches_pattern("a?c", "abc"));
+        assert!(matches_pattern("*b*b", "abcbab"));
+        assert!(!matches_pattern("a?c", "ac"));
+        assert!(!matches_pattern("cosmos1*", "osmo1abc"));
+        assert!(!matches_pattern("*b", "abc"));
+    }
+
+    #[test]
+    fn constraint_allows() {
+        let range = Constraint::Range {
+            min: Some(Uint128::new(1)),
+            max: Some(Uint128::new(3)),
+        };
+        assert!(range.allows(&Primitive::Uint128(Uint128::new(3))));
+        assert!(!range.allows(&Primitive::Uint128(Uint128::new(4))));
+        // Rules do not concern values of other types.
+        assert!(range.allows(&Primitive::Bool(true)));
+
+        let one_of = Constraint::OneOf {
+            values: vec![Primitive::String("a".to_string())],
+        };
+        assert!(one_of.allows(&Primitive::String("a".to_string())));
+        assert!(!one_of.allows(&Primitive::String("b".to_string())));
+
+        let max_length = Constraint::MaxLength { max: 2 };
+        assert!(max_length.allows(&Primitive::String("ab".to_string())));
+        assert!(!max_length.allows(&Primitive::String("abc".to_string())));
+        // MaxLength counts bytes and MaxChars characters.
+        let emoji = Primitive::String("😀😀".to_string());
+        assert!(!max_length.allows(&emoji));
+        assert!(Constraint::MaxChars { max: 2 }.allows(&emoji));
+        assert!(!Constraint::MaxChars { max: 1 }.allows(&emoji));
+
+        let max_items = Constraint::MaxItems { max: 2 };
+        assert!(max_items.allows_len(2));
+        assert!(!max_items.allows_len(3));
+        assert!(range.allows_len(3));
+    }
+
+    #[test]
+    fn key_schema_rules() {
+        let schema = json_schema::parse(
+            r#"{"type":"array","items":{"type":"integer","minimum":1,"maxLength":3}}"#,
+        )
+        .unwrap();
+        assert_eq!(None, schema.invalid_reason());
+        assert_eq!(
+            Some(TypeLock {
+                r#type: PrimitiveType::Vec,
+                element_type: Some(PrimitiveType::Uint128),
+            }),
+            schema.type_lock()
+        );
+        assert!(schema.constraints().is_empty());
+        assert_eq!(
+            vec![
+                Constraint::Range {
+                    min: Some(Uint128::new(1)),
+                    max: None
+                },
+                Constraint::MaxChars { max: 3 },
+            ],
+            schema.item_constraints()
+        );
+        let array_enum = json_schema::parse(r#"{"type":"array","enum":[1]}"#).unwrap();
+        assert!(array_enum.invalid_reason().is_some());
+
+        let nested = json_schema::parse(r#"{"type":"array","items":{"type":"array"}}"#).unwrap();
+        assert_eq!(Some("arrays cannot be nested"), nested.invalid_reason());
+        let items = json_schema::parse(r#"{"type":"string","items":{}}"#).unwrap();
+        assert_eq!(
+            Some("items is only allowed for the array type"),
+            items.invalid_reason()
+        );
+        let range = json_schema::parse(r#"{"minimum":2,"maximum":1}"#).unwrap();
+        assert!(range.invalid_reason().is_some());
+    }
+
+    #[test]
+    fn key_rules_reasons() {
+        let rules = KeyRules {
+            max_length: 12,
+            allowed_symbols: Some("_-".to_string()),
+        };
+        assert_eq!(None, rules.invalid_reason("oracle/btc_1"));
+        assert_eq!(
+            Some("name must not be empty".to_string()),
+            rules.invalid_reason("")
+        );
+        assert_eq!(
+            Some("name is longer than 12 bytes".to_string()),
+            rules.invalid_reason("oracle/btc_12")
+        );
+        assert_eq!(
+            Some("name must not start or end with a separator".to_string()),
+            rules.invalid_reason("oracle/")
+        );
+        assert_eq!(
+            Some("name must not contain empty segments".to_string()),
+            rules.invalid_reason("a//b")
+        );
+        assert_eq!(
+            Some("name must not contain ' '".to_string()),
+            rules.invalid_reason("a b")
+        );
+        assert_eq!(None, KeyRules::default().invalid_reason("a b"));
+    }
+
+    #[test]
+    fn record_change_keeps_first_value() {
+        let mut storage = MockStorage::new();
+        save_primitive(&mut storage, "a", &Primitive::Bool(true)).unwrap();
+        // Nothing is recorded before the first snapshot.
+        record_change(&mut storage, "a").unwrap();
+        assert_eq!(
+            None,
+            SNAPSHOT_CHANGED_KEYS
+                .prefix("a")
+                .keys(&storage, None, None, Order::Ascending)
+                .next()
+        );
+
+        let snapshot = Snapshot {
+            id: 1,
+            label: "first".to_string(),
+            height: 1,
+            time: Timestamp::from_seconds(0),
+        };
+        SNAPSHOTS
+            .save(&mut storage, U64Key::new(1), &snapshot)
+            .unwrap();
+        SNAPSHOT_COUNT.save(&mut storage, &1).unwrap();
+        record_change(&mut storage, "a").unwrap();
+        save_primitive(&mut storage, "a", &Primitive::Bool(false)).unwrap();
+        record_change(&mut storage, "a").unwrap();
+        record_change(&mut storage, "b").unwrap();
+        assert_eq!(
+            Some(Primitive::Bool(true)),
+            SNAPSHOT_CHANGES
+                .load(&storage, (U64Key::new(1), "a"))
+                .unwrap()
+        );
+        assert_eq!(
+            None,
+            SNAPSHOT_CHANGES
+                .load(&storage, (U64Key::new(1), "b"))
+                .unwrap()
+        );
+        assert!(SNAPSHOT_CHANGED_KEYS.has(&storage, ("a", U64Key::new(1))));
+
+        assert_eq!(
+            (7, "a/b".to_string()),
+            parse_change_key(&change_key(7, "a/b")).unwrap()
+        );
+    }
 }